@@ -5,17 +5,20 @@ use palette::Srgb;
 use rand::{Rng, rng, rngs::ThreadRng};
 
 use rgb_engine::{
-    Bounds, Effect,
+    Bounds, Coordinates, Effect,
     effects::perlin::{Direction, PerlinWave},
     keyboard::{
-        DeltaWatcher, KeyboardMatrix, get_matrix,
+        KeyEvent, KeyEventKind, KeyboardMatrix, get_matrix,
         matrix::{self, ESC, SPACE, compute_bounds},
     },
-    runtime::{ColorBlendTypes, Process, Runtime},
+    runtime::{ColorBlendTypes, Key, Process, Runtime},
 };
 
 fn main() {
-    Runtime::new(true).run(&mut FlappyBird::new());
+    let mut runtime = Runtime::new(true);
+    // Keep the game ticking while the keyboard is updated
+    runtime.render_in_background();
+    runtime.run(&mut FlappyBird::new());
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -40,6 +43,8 @@ struct GameConfig {
     bird_spawn_pos: Vector2<f64>,
     score_color: Srgb<f64>,
     pipe_spawn_y_offset: f64,
+    /// How far outside the bird and pipes keys still get some color
+    soft_edge: f64,
 }
 
 struct World {
@@ -78,7 +83,9 @@ pub struct FlappyBird {
     config: GameConfig,
     current_state: GameState,
     score: u32,
-    delta_watcher: DeltaWatcher,
+    /// Keys pressed since the last process call
+    jumped: bool,
+    esc_pressed: bool,
     grass: PerlinWave,
     sky: PerlinWave,
 }
@@ -122,9 +129,11 @@ impl FlappyBird {
                 bird_spawn_pos: Vector2::new(-0.2, 0.0),
                 score_color: Srgb::new(1.0, 1.0, 1.0),
                 pipe_spawn_y_offset: -0.4,
+                soft_edge: 0.15,
             },
             current_state: GameState::MainMenu,
-            delta_watcher: DeltaWatcher::dummy(),
+            jumped: false,
+            esc_pressed: false,
             grass: PerlinWave::new(0, 0.1, 2.0),
             sky: PerlinWave::new(1, 0.05, 1.0),
             score: 0,
@@ -137,27 +146,43 @@ impl FlappyBird {
             key.color_blend_type = ColorBlendTypes::Nothing;
             let bird = &self.world.bird;
 
-            if bird
-                .bounds
-                .contains(key.pos_norm_aspect.rotate(RotateDirection::AntiClockWise))
-            {
+            let alpha = self.coverage(key, &bird.bounds);
+            if alpha > 0.0 {
                 key.color = self.world.bird.current_color;
                 key.color_blend_type = ColorBlendTypes::Mask;
+                key.alpha = alpha;
             }
         }
     }
 
+    /// Alpha of a key the given distance away from a sprite.
+    fn edge_alpha(&self, distance: f64) -> f64 {
+        (1.0 - distance / self.config.soft_edge).clamp(0.0, 1.0)
+    }
+
+    /// How much of the key the sprite covers.
+    fn coverage(&self, key: &Key, bounds: &Bounds) -> f64 {
+        let points = Coordinates::AspectCorrected.sample_points(key, 3);
+        points
+            .iter()
+            .map(|point| {
+                self.edge_alpha(bounds.distance(point.rotate(RotateDirection::AntiClockWise)))
+            })
+            .sum::<f64>()
+            / points.len() as f64
+    }
+
     fn render_pipes(&self, layer: &mut KeyboardMatrix) {
         for key in layer.as_flattened_mut() {
             key.color = Srgb::new(0.0, 0.0, 0.0);
             key.color_blend_type = ColorBlendTypes::Nothing;
+            key.alpha = 0.0;
             for pipe in self.world.pipes.iter() {
-                if pipe
-                    .bounds
-                    .contains(key.pos_norm_aspect.rotate(RotateDirection::AntiClockWise))
-                {
+                let alpha = self.coverage(key, &pipe.bounds);
+                if alpha > key.alpha {
                     key.color = self.config.pipe_color;
                     key.color_blend_type = ColorBlendTypes::Mask;
+                    key.alpha = alpha;
                 }
             }
         }
@@ -244,7 +269,9 @@ impl Process for FlappyBird {
         //     self.world.bounds.position,
         //     self.world.bounds.position + self.world.bounds.size
         // );
-        self.delta_watcher = runtime.delta_watcher.clone().unwrap();
+        if runtime.delta_watcher.is_none() {
+            println!("Please enable analog to play");
+        }
         self.world.bird.bounds.position = self.config.bird_spawn_pos;
         self.world.bird.current_color = self.config.bird_color;
         self.grass.hue_offset = 100.0;
@@ -258,6 +285,7 @@ impl Process for FlappyBird {
         runtime.create_layer(0, get_matrix());
         runtime.create_layer(-1, get_matrix());
         runtime.create_layer(-2, get_matrix());
+        runtime.scheduler.render_rate = Some(60.0);
 
         runtime.create_timer(
             Duration::from_secs_f64(self.config.spawn_interval),
@@ -274,7 +302,7 @@ impl Process for FlappyBird {
             Duration::from_millis(100),
             false,
             move |runtime, process| {
-                let elapsed = runtime.start.elapsed().as_secs_f64();
+                let elapsed = runtime.elapsed().as_secs_f64();
                 for key in runtime.get_layer(-2).as_flattened_mut() {
                     if key.pos_norm_aspect.x < -1.2 {
                         key.color = process.grass.color(elapsed, key.pos_norm_aspect) * 0.3;
@@ -290,22 +318,19 @@ impl Process for FlappyBird {
         );
     }
 
+    fn input(&mut self, _runtime: &mut Self::Owner, event: KeyEvent) {
+        if event.kind == KeyEventKind::Pressed {
+            match event.key {
+                SPACE => self.jumped = true,
+                ESC => self.esc_pressed = true,
+                _ => (),
+            }
+        }
+    }
+
     fn process(&mut self, runtime: &mut Self::Owner, delta: Duration) {
-        runtime.update_keyboard();
-        let just_jumped = self
-            .delta_watcher
-            .keys
-            .iter()
-            .find(|key| key.key == SPACE)
-            .unwrap()
-            .just_pressed();
-        let esc_pressed = self
-            .delta_watcher
-            .keys
-            .iter()
-            .find(|key| key.key == ESC)
-            .unwrap()
-            .just_pressed();
+        let just_jumped = std::mem::take(&mut self.jumped);
+        let esc_pressed = std::mem::take(&mut self.esc_pressed);
 
         match self.current_state {
             GameState::MainMenu => {
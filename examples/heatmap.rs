@@ -0,0 +1,50 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use rgb_engine::{
+    Coordinates,
+    effects::analog::Heatmap,
+    keyboard::{KeyEvent, KeyEventKind, matrix::ESC},
+    runtime::{Process, Runtime},
+};
+
+/// Usage is kept here between runs
+const FILE: &str = "heatmap.csv";
+
+fn main() {
+    Runtime::new(true).run(&mut Usage::default());
+}
+
+/// Shows the most used keys. Press esc to export the counts to heatmap.json.
+#[derive(Default)]
+pub struct Usage {
+    heatmap: Option<Rc<RefCell<Heatmap>>>,
+}
+
+impl Process for Usage {
+    type Owner = Runtime<Self>;
+
+    fn init(&mut self, runtime: &mut Self::Owner) {
+        let Some(delta_watcher) = runtime.delta_watcher.clone() else {
+            panic!("Please enable analog feature");
+        };
+        runtime.scheduler.render_rate = Some(30.0);
+
+        let heatmap = Rc::new(RefCell::new(Heatmap::with_file(delta_watcher, FILE)));
+        runtime.bind_effect(0, heatmap.clone(), Coordinates::AspectCorrected);
+        self.heatmap = Some(heatmap);
+    }
+
+    fn process(&mut self, _runtime: &mut Self::Owner, _delta: Duration) {}
+
+    fn input(&mut self, _runtime: &mut Self::Owner, event: KeyEvent) {
+        if event.kind == KeyEventKind::Pressed
+            && event.key == ESC
+            && let Some(heatmap) = &self.heatmap
+        {
+            match heatmap.borrow().export_json("heatmap.json") {
+                Ok(()) => println!("Exported heatmap.json"),
+                Err(err) => println!("Could not export: {err}"),
+            }
+        }
+    }
+}
@@ -1,19 +1,18 @@
 use std::{
     sync::atomic::Ordering::Relaxed,
-    thread::sleep,
     time::{Duration, Instant},
 };
 
 use lerp::Lerp;
 use palette::{ClampAssign, Srgb};
 use rgb_engine::{
-    Effect,
+    Coordinates, Effect,
     effects::{
         Ripple,
         analog::{Area, KeyFilter, LocalPressBrightness},
         perlin::{Direction, PerlinWave},
     },
-    keyboard::{DeltaWatcher, KeyDelta, get_matrix, matrix::FN},
+    keyboard::{DeltaWatcher, KeyDelta, KeyGroup, get_matrix},
     runtime::{ColorBlendTypes, Key, Process, Runtime},
 };
 
@@ -21,6 +20,7 @@ static LED_TIMEOUT: Duration = Duration::from_mins(5);
 
 const MAX_REVEALED: f64 = 5.0;
 const RIPPLE_LAYER: i32 = 100;
+const IDLE_LAYER: i32 = 1_000_000;
 const BETWEEN_MAIN_FN: f64 = 0.3;
 const ANIMATION_SMOOTHENING: f64 = 1.5;
 const ENERGY_DECAY: f64 = 5.0;
@@ -31,8 +31,6 @@ fn main() {
 }
 
 pub struct PinkRipple {
-    pub background: PerlinWave,
-    pub ripple: Ripple,
     pub reveal: Area,
     pub brightness: LocalPressBrightness,
     last_key_press: Instant,
@@ -43,13 +41,7 @@ pub struct PinkRipple {
 
 impl Default for PinkRipple {
     fn default() -> Self {
-        let mut background = PerlinWave::new(0, 0.5, 0.8);
-        background.hue_range = 30.0;
-        background.hue_offset = 300.0;
-        background.direction = Direction::Depth;
         Self {
-            background,
-            ripple: Ripple::new(DeltaWatcher::dummy(), 5.0, 2.0, 2.0, 0.3, true),
             reveal: Area::new(DeltaWatcher::dummy(), MAX_REVEALED, 1.0),
             current_revealed: 0.0,
             fn_key: (Key::default(), KeyDelta::default()),
@@ -64,24 +56,39 @@ impl Process for PinkRipple {
     type Owner = Runtime<Self>;
 
     fn init(&mut self, runtime: &mut Self::Owner) {
+        let mut background = PerlinWave::new(0, 0.5, 0.8);
+        background.hue_range = 30.0;
+        background.hue_offset = 300.0;
+        background.direction = Direction::Depth;
+        let mut ripple = Ripple::new(DeltaWatcher::dummy(), 5.0, 2.0, 2.0, 0.3, true);
+
         if let Some(delta_watcher) = &runtime.delta_watcher {
-            self.ripple.delta_watcher = delta_watcher.clone();
+            ripple.delta_watcher = delta_watcher.clone();
             self.reveal.delta_watcher = delta_watcher.clone();
-            self.reveal.filter = KeyFilter::Included(vec![(FN)]);
+            self.reveal.filter = KeyFilter::include_labels(&["Fn"]);
             self.brightness.delta_watcher = delta_watcher.clone();
-            self.brightness.filter = KeyFilter::Included(vec![FN]);
+            self.brightness.filter = KeyFilter::include_labels(&["Fn"]);
         } else {
             println!("Please enable analog for full functionality");
         }
 
-        runtime.create_layer(1_000_000, get_matrix());
+        // Black mask over everything that is shown while idle
+        let mut layer = get_matrix();
+        for ele in layer.as_flattened_mut() {
+            ele.color_blend_type = ColorBlendTypes::Mask;
+        }
+        runtime.create_layer(IDLE_LAYER, layer);
+        runtime.set_layer_visible(IDLE_LAYER, false);
 
         let mut layer = get_matrix();
         for ele in layer.as_flattened_mut() {
             ele.color_blend_type = ColorBlendTypes::Mult;
         }
         runtime.create_layer(RIPPLE_LAYER, layer);
-        runtime.create_layer(0, get_matrix());
+        runtime.bind_effect(RIPPLE_LAYER, ripple, Coordinates::AspectCorrected);
+        // Average the ripple over wide keys like space
+        runtime.set_supersampling(RIPPLE_LAYER, 3);
+        runtime.bind_effect(0, background, Coordinates::AspectCorrected);
         let fn_layer = {
             let red = Srgb::new(1.0, 0.0, 0.0);
             let green = Srgb::new(0.0, 1.0, 0.0);
@@ -95,84 +102,57 @@ impl Process for PinkRipple {
             for ele in layer.as_flattened_mut() {
                 ele.color_blend_type = ColorBlendTypes::Nothing;
             }
-            layer[1][0].color = blue;
-            for ele in layer[1][1..=12].iter_mut() {
-                ele.color = green;
-            }
-            layer[1][13].color = red;
+            layer.select_labels(&["Esc"]).set_color(blue);
+            layer.select(KeyGroup::Digits).set_color(green);
+            layer.select_labels(&["+", "´"]).set_color(green);
+            layer.select_labels(&["Backspace"]).set_color(red);
 
-            layer[2][0].color = cyan;
-            layer[2][1].color = red;
-            layer[2][2].color = white;
-            layer[2][3].color = green;
-            layer[2][8].color = yellow;
-            layer[2][9].color = pink;
-            for ele in layer[2][10..=12].iter_mut() {
-                ele.color = red;
-            }
-
-            layer[3][0].color = red;
-            for ele in layer[3][1..=3].iter_mut() {
-                ele.color = white;
-            }
-            for ele in layer[3][7..=9].iter_mut() {
-                ele.color = yellow;
-            }
-            layer[3][10].color = white * 0.1;
-            layer[3][11].color = white;
-            layer[3][12].color = cyan;
-            layer[3][13].color = green;
-
-            layer[4][0].color = pink;
-            layer[4][8].color = yellow;
-            layer[4][9].color = red;
-            layer[4][10].color = green;
-            layer[4][11].color = white;
-            layer[4][13].color = cyan;
+            layer.select_labels(&["Tab", "'"]).set_color(cyan);
+            layer
+                .select_labels(&["Q", "P", "Å", "¨", "CapsLock"])
+                .set_color(red);
+            layer.select(KeyGroup::Wasd).set_color(white);
+            layer.select_labels(&["E", "Enter"]).set_color(green);
+            layer.select_labels(&["I", "J", "K", "L"]).set_color(yellow);
+            layer.select_labels(&["O"]).set_color(pink);
+            layer.select_labels(&["Ö"]).set_color(white * 0.1);
+            layer.select_labels(&["Ä"]).set_color(white);
+
+            layer.select_labels(&["M"]).set_color(yellow);
+            layer.select_labels(&[","]).set_color(red);
+            layer.select_labels(&["."]).set_color(green);
+            layer.select_labels(&["-"]).set_color(white);
 
-            layer[5][0].color = pink;
-            layer[5][1].color = white;
-            for ele in layer[5][2..=9].iter_mut() {
-                ele.color = pink;
-            }
-            for ele in layer[5][10..=12].iter_mut() {
-                ele.color = white;
-            }
-            layer[5][13].color = cyan;
+            layer
+                .select_labels(&["LeftShift", "LeftControl", "LeftAlt", "Space"])
+                .set_color(pink);
+            layer
+                .select_labels(&["LeftMod", "AltGr", "RightMod", "RightControl"])
+                .set_color(white);
+            layer.select_labels(&["RightShift", "Fn"]).set_color(cyan);
 
             layer
         };
-        runtime.create_layer(200, fn_layer);
+        runtime.create_layer(200, fn_layer.clone());
         let mut layer = get_matrix();
         for ele in layer.as_flattened_mut() {
             ele.color_blend_type = ColorBlendTypes::Sub;
         }
         runtime.create_layer(150, layer);
 
-        runtime.create_layer(-100, fn_layer);
-        if let Some(delta_watcher) = &runtime.delta_watcher {
-            self.fn_key = (
-                fn_layer[5][13],
-                delta_watcher
-                    .keys
-                    .iter()
-                    .find(|dkey| dkey.key == fn_layer[5][13].key)
-                    .unwrap()
-                    .clone(),
-            );
+        runtime.create_layer(-100, fn_layer.clone());
+        if let Some(delta_watcher) = &runtime.delta_watcher
+            && let Some(fn_key) = fn_layer.by_label("Fn")
+            && let Some(fn_delta) = delta_watcher
+                .keys
+                .iter()
+                .find(|dkey| dkey.key == fn_key.key)
+        {
+            self.fn_key = (*fn_key, fn_delta.clone());
         }
 
         runtime.create_timer(Duration::from_millis(50), false, move |runtime, process| {
             if !process.idle {
-                let elapsed = runtime.start.elapsed().as_secs_f64();
-                for key in runtime.get_layer(0).as_flattened_mut() {
-                    key.color = process.background.color(elapsed, key.pos_norm_aspect);
-                }
-
-                for key in runtime.get_layer(RIPPLE_LAYER).as_flattened_mut() {
-                    key.color = process.ripple.color(elapsed, key.pos_norm_aspect);
-                }
-
                 for key in runtime.get_layer(200).as_flattened_mut() {
                     let distance_to_fn = key
                         .pos_norm_aspect
@@ -192,7 +172,6 @@ impl Process for PinkRipple {
                         delta * 10.0,
                     );
                 }
-                runtime.update_keyboard();
             }
             true
         });
@@ -200,8 +179,6 @@ impl Process for PinkRipple {
 
     fn process(&mut self, runtime: &mut Self::Owner, delta: Duration) {
         if !self.idle {
-            sleep(Duration::from_millis(10).saturating_sub(delta));
-
             for i in 0..runtime.get_layer(200).as_flattened().len() {
                 let other = runtime.get_layer(-100).as_flattened()[i];
                 let key = &mut runtime.get_layer(200).as_flattened_mut()[i];
@@ -226,11 +203,6 @@ impl Process for PinkRipple {
                 delta.as_secs_f64() * ANIMATION_SMOOTHENING,
             ) - 0.001;
             self.current_revealed = self.current_revealed.clamp(0.0, 1.0);
-
-            self.ripple.update(runtime.start.elapsed().as_secs_f64());
-        } else {
-            sleep(Duration::from_millis(200).saturating_sub(delta));
-            runtime.update_keyboard();
         }
 
         if let Some(delta_watcher) = &runtime.delta_watcher {
@@ -242,14 +214,11 @@ impl Process for PinkRipple {
         }
 
         let idle = self.last_key_press.elapsed() > LED_TIMEOUT;
-        for key in runtime.get_layer(1_000_000).as_flattened_mut() {
-            key.color_blend_type = if idle {
-                ColorBlendTypes::Mask
-            } else {
-                ColorBlendTypes::Nothing
-            }
-        }
+        runtime.set_layer_visible(IDLE_LAYER, idle);
         self.idle = idle;
+        // Slow everything down while idle
+        runtime.scheduler.process_rate = Some(if idle { 5.0 } else { 100.0 });
+        runtime.scheduler.render_rate = Some(if idle { 5.0 } else { 20.0 });
         if let Some(delta_watcher) = &runtime.delta_watcher {
             delta_watcher.idle.store(idle, Relaxed);
         }
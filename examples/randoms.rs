@@ -1,4 +1,4 @@
-use std::{thread::sleep, time::Duration};
+use std::time::Duration;
 
 use rand::{Rng, rngs::ThreadRng};
 use rgb_engine::{
@@ -33,22 +33,19 @@ impl Process for Randoms {
 
         runtime.create_layer(0, get_matrix());
         runtime.create_timer(Duration::from_millis(20), false, |r, p| {
-            let layer = r.get_layer(0).as_flattened_mut();
-            let i = p.rng.random_range(0..layer.len());
-            let key = &mut layer[i];
-            key.color = p.get_random_color();
+            let mut keys = r.get_layer(0).keys_mut().collect::<Vec<_>>();
+            let i = p.rng.random_range(0..keys.len());
+            keys[i].color = p.get_random_color();
             true
         });
 
-        runtime.create_timer(Duration::from_millis(50), false, |r, _p| {
-            r.update_keyboard();
-            true
-        });
+        // Smooth out the end of the fades.
+        // Dithering needs a high frame rate to not flicker.
+        runtime.dithering = true;
+        runtime.scheduler.render_rate = Some(60.0);
     }
 
     fn process(&mut self, runtime: &mut Self::Owner, delta: std::time::Duration) {
-        sleep(Duration::from_millis(10).saturating_sub(delta));
-
         for key in runtime.get_layer(0).as_flattened_mut() {
             key.color = key
                 .color
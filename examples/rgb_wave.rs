@@ -1,8 +1,7 @@
 use std::time::Duration;
 
 use rgb_engine::{
-    Effect,
-    keyboard::get_matrix,
+    Coordinates, Effect,
     re_exports::{
         nalgebra::Vector2,
         palette::{Hsv, IntoColor, Srgb},
@@ -11,7 +10,7 @@ use rgb_engine::{
 };
 
 fn main() {
-    Runtime::new(false).run(&mut WaveProcess::new(Wave::new(-0.2, 0.4)));
+    Runtime::new(false).run(&mut WaveProcess);
 }
 
 struct Wave {
@@ -32,29 +31,16 @@ impl Effect for Wave {
     }
 }
 
-struct WaveProcess {
-    effect: Wave,
-}
-
-impl WaveProcess {
-    fn new(effect: Wave) -> Self {
-        Self { effect }
-    }
-}
+struct WaveProcess;
 
 impl Process for WaveProcess {
     type Owner = Runtime<Self>;
 
     fn init(&mut self, runtime: &mut Self::Owner) {
-        runtime.create_layer(0, get_matrix());
+        // The runtime renders the wave to layer 0 on its own
+        runtime.bind_effect(0, Wave::new(-0.2, 0.4), Coordinates::Normalized);
+        runtime.scheduler.render_rate = Some(30.0);
     }
 
-    fn process(&mut self, runtime: &mut Self::Owner, _delta: Duration) {
-        let elapsed = runtime.start.elapsed().as_secs_f64();
-        for key in runtime.get_layer(0).as_flattened_mut() {
-            key.color = self.effect.color(elapsed, key.pos_norm);
-        }
-
-        runtime.update_keyboard();
-    }
+    fn process(&mut self, _runtime: &mut Self::Owner, _delta: Duration) {}
 }
@@ -0,0 +1,258 @@
+//! Builds a layout file for a keyboard by pressing the keys one at a time.
+//!
+//! Usage: `cargo run --bin calibrate -- <output file> [base layout]`
+//!
+//! Every matrix position is lit one at a time. Press the lit key to record its scan code.
+//! Positions without a LED or a key can be skipped by pressing enter in the terminal.
+//! Type `q` and press enter to stop and save what has been recorded.
+//! A key is only recorded once nothing has been typed for a moment after releasing it,
+//! so typing commands doesn't record their keys.
+//! Since enter skips, record the enter key with another window focused so it isn't typed here.
+//!
+//! Geometry and labels are taken from the base layout if it has the key.
+//! The base layout can be `60he`, `two_he`, `80he`, `uwu` or a path to a layout file.
+//! With a base layout only its keys are calibrated, otherwise every matrix position is.
+
+use std::{
+    env,
+    io::{self, BufRead},
+    process::exit,
+    sync::mpsc::{Receiver, TryRecvError, channel},
+    thread::{self, sleep},
+    time::{Duration, Instant},
+};
+
+use nalgebra::Vector2;
+use rgb_engine::{
+    keyboard::{AnalogSource, Layout, LayoutKey, WootingAnalog, matrix::RAW_MATRIX},
+    output::{KeyColor, OutputBackend, WootingBackend},
+};
+
+/// How far the key has to be pressed to count
+const PRESS_DISTANCE: f32 = 0.5;
+/// How long it takes for a key press to show up in the terminal
+const TERMINAL_DELAY: Duration = Duration::from_millis(50);
+/// How long to wait after a key is released for more typing before recording it
+const TYPING_DELAY: Duration = Duration::from_millis(500);
+
+enum Command {
+    Skip,
+    Quit,
+}
+
+/// What the user did for the lit position.
+enum Input {
+    Key(u16),
+    Command(Command),
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let Some(output) = args.next() else {
+        println!("Usage: calibrate <output file> [base layout]");
+        exit(1);
+    };
+    let base = match args.next().as_deref() {
+        None => None,
+        Some("60he") => Some(Layout::wooting_60he()),
+        Some("two_he") => Some(Layout::wooting_two_he()),
+        Some("80he") => Some(Layout::wooting_80he()),
+        Some("uwu") => Some(Layout::wooting_uwu()),
+        Some(path) => match Layout::load(path) {
+            Ok(layout) => Some(layout),
+            Err(err) => {
+                println!("Could not load base layout: {err}");
+                exit(1);
+            }
+        },
+    };
+
+    let mut backend = WootingBackend::new();
+    if !backend.is_connected() {
+        println!("No keyboard connected");
+        exit(1);
+    }
+    let mut analog = WootingAnalog::new();
+    if let Err(err) = analog.init() {
+        println!("{err}");
+        exit(1);
+    }
+
+    let positions = match &base {
+        Some(base) => base.keys.iter().map(|key| key.matrix).collect::<Vec<_>>(),
+        None => RAW_MATRIX.as_flattened().to_vec(),
+    };
+    let commands = read_commands();
+
+    let mut layout = Layout {
+        name: base
+            .as_ref()
+            .map(|base| base.name.clone())
+            .unwrap_or_default(),
+        frame: base.as_ref().and_then(|base| base.frame),
+        keys: Vec::new(),
+    };
+
+    println!("Press the lit key. Press enter to skip it or type q and enter to finish.");
+    'positions: for (i, position) in positions.iter().copied().enumerate() {
+        light(&mut backend, position);
+        println!("[{}/{}] {position:?}", i + 1, positions.len());
+
+        let scan_code = loop {
+            match next_input(&mut analog, &commands) {
+                Input::Key(scan_code) => {
+                    if let Some(other) = layout
+                        .keys
+                        .iter()
+                        .find(|key| key.scan_codes.contains(&scan_code))
+                    {
+                        println!(
+                            "Scan code {scan_code} is already recorded for {:?}, press the lit key",
+                            other.matrix
+                        );
+                        skip_input(&mut analog, &commands);
+                        continue;
+                    }
+                    break scan_code;
+                }
+                Input::Command(Command::Skip) => {
+                    // Enter is still held, don't record it for the next position
+                    skip_input(&mut analog, &commands);
+                    continue 'positions;
+                }
+                Input::Command(Command::Quit) => break 'positions,
+            }
+        };
+
+        let base_key = base.as_ref().and_then(|base| base.key(position));
+        match layout.keys.iter_mut().find(|key| key.matrix == position) {
+            Some(key) => key.scan_codes.push(scan_code),
+            None => layout.keys.push(LayoutKey {
+                matrix: position,
+                position: base_key
+                    .map(|key| key.position)
+                    .unwrap_or(Vector2::new(position.1 as f64, position.0 as f64)),
+                size: base_key
+                    .map(|key| key.size)
+                    .unwrap_or(Vector2::new(1.0, 1.0)),
+                rotation: base_key.map(|key| key.rotation).unwrap_or_default(),
+                label: base_key
+                    .map(|key| key.label.clone())
+                    .unwrap_or(format!("{}_{}", position.0, position.1)),
+                scan_codes: vec![scan_code],
+            }),
+        }
+        println!("Recorded scan code {scan_code}");
+        // The key press is typed into the terminal too, don't take it as a command
+        skip_input(&mut analog, &commands);
+    }
+
+    analog.close();
+    backend.set_frame(&frame(None));
+    backend.flush();
+
+    match layout.save(&output) {
+        Ok(()) => println!("Saved {} keys to {output}", layout.keys.len()),
+        Err(err) => println!("Could not save the layout: {err}"),
+    }
+}
+
+/// Every matrix position turned off except the lit one.
+fn frame(lit: Option<(u8, u8)>) -> Vec<KeyColor> {
+    RAW_MATRIX
+        .as_flattened()
+        .iter()
+        .map(|key| {
+            let color = if Some(*key) == lit {
+                (255, 255, 255)
+            } else {
+                (0, 0, 0)
+            };
+            (*key, color)
+        })
+        .collect()
+}
+
+fn light(backend: &mut WootingBackend, position: (u8, u8)) {
+    backend.set_frame(&frame(Some(position)));
+    backend.flush();
+}
+
+/// Waits for a key press or a command.
+/// Keys typed into the terminal are ignored once the command they are part of arrives.
+fn next_input(analog: &mut WootingAnalog, commands: &Receiver<Command>) -> Input {
+    let mut scan_code = None;
+    let mut deadline = Instant::now();
+    loop {
+        match commands.try_recv() {
+            Ok(command) => return Input::Command(command),
+            Err(TryRecvError::Disconnected) => return Input::Command(Command::Quit),
+            Err(TryRecvError::Empty) => (),
+        }
+        if let Some(pressed) = pressed(analog) {
+            // Might be typing, give the rest of the command time to arrive
+            scan_code = Some(pressed);
+            wait_for_release(analog);
+            sleep(TERMINAL_DELAY);
+            deadline = Instant::now() + TYPING_DELAY;
+            continue;
+        }
+        if let Some(scan_code) = scan_code
+            && Instant::now() >= deadline
+        {
+            return Input::Key(scan_code);
+        }
+        sleep(Duration::from_millis(5));
+    }
+}
+
+/// Scan code of a key that is pressed down if any.
+fn pressed(analog: &mut WootingAnalog) -> Option<u16> {
+    let mut samples = Vec::new();
+    if let Err(err) = analog.read(&mut samples) {
+        println!("Error while reading values: {err}");
+        exit(1);
+    }
+    samples
+        .iter()
+        .find(|(_, distance)| *distance >= PRESS_DISTANCE)
+        .map(|(code, _)| *code)
+}
+
+fn wait_for_release(analog: &mut WootingAnalog) {
+    let mut samples = Vec::new();
+    loop {
+        samples.clear();
+        if analog.read(&mut samples).is_err()
+            || samples.iter().all(|(_, distance)| *distance <= 0.0)
+        {
+            return;
+        }
+        sleep(Duration::from_millis(5));
+    }
+}
+
+/// Wait for every key to be released and forget the commands typed meanwhile.
+fn skip_input(analog: &mut WootingAnalog, commands: &Receiver<Command>) {
+    wait_for_release(analog);
+    sleep(TERMINAL_DELAY);
+    while commands.try_recv().is_ok() {}
+}
+
+/// Commands typed into the terminal.
+fn read_commands() -> Receiver<Command> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let command = match line.as_deref().map(str::trim) {
+                Ok("q") => Command::Quit,
+                Ok(_) => Command::Skip,
+                Err(_) => break,
+            };
+            if sender.send(command).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
@@ -0,0 +1,125 @@
+use palette::{Hsv, IntoColor, Srgb};
+
+use crate::key::ColorBlendTypes;
+
+type HsvColor = Hsv<palette::encoding::Srgb, f64>;
+
+/// A way to blend the color of a key on top of the color below it.
+/// Register your own with `Runtime::register_blend_mode` and use it through
+/// `ColorBlendTypes::Custom`.
+pub trait BlendMode {
+    /// Color of `upper` blended on top of `lower`.
+    fn blend(&self, lower: Srgb<f64>, upper: Srgb<f64>) -> Srgb<f64>;
+}
+
+impl<F: Fn(Srgb<f64>, Srgb<f64>) -> Srgb<f64>> BlendMode for F {
+    fn blend(&self, lower: Srgb<f64>, upper: Srgb<f64>) -> Srgb<f64> {
+        self(lower, upper)
+    }
+}
+
+impl BlendMode for ColorBlendTypes {
+    /// Custom blend modes can't be resolved without the runtime and leave the color as is.
+    fn blend(&self, lower: Srgb<f64>, upper: Srgb<f64>) -> Srgb<f64> {
+        match self {
+            ColorBlendTypes::Add => lower + upper,
+            ColorBlendTypes::Sub => lower - upper,
+            ColorBlendTypes::Mult => Srgb::new(
+                lower.red * upper.red,
+                lower.green * upper.green,
+                lower.blue * upper.blue,
+            ),
+            ColorBlendTypes::AlphaBlend(lower_mult, upper_mult) => {
+                lower * *lower_mult + upper * *upper_mult
+            }
+            ColorBlendTypes::Mask => upper,
+            ColorBlendTypes::Nothing | ColorBlendTypes::Custom(_) => lower,
+            ColorBlendTypes::Screen => separable(lower, upper, |a, b| 1.0 - (1.0 - a) * (1.0 - b)),
+            ColorBlendTypes::Overlay => separable(lower, upper, |a, b| hard_light(b, a)),
+            ColorBlendTypes::HardLight => separable(lower, upper, hard_light),
+            ColorBlendTypes::SoftLight => separable(lower, upper, soft_light),
+            ColorBlendTypes::Lighten => separable(lower, upper, f64::max),
+            ColorBlendTypes::Darken => separable(lower, upper, f64::min),
+            ColorBlendTypes::Difference => separable(lower, upper, |a, b| (a - b).abs()),
+            ColorBlendTypes::ColorDodge => separable(lower, upper, |a, b| {
+                if a <= 0.0 {
+                    0.0
+                } else if b >= 1.0 {
+                    1.0
+                } else {
+                    (a / (1.0 - b)).min(1.0)
+                }
+            }),
+            ColorBlendTypes::ColorBurn => separable(lower, upper, |a, b| {
+                if a >= 1.0 {
+                    1.0
+                } else if b <= 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - a) / b).min(1.0)
+                }
+            }),
+            ColorBlendTypes::Hue => hsv(lower, upper, |lower, upper| {
+                Hsv::new(upper.hue, lower.saturation, lower.value)
+            }),
+            ColorBlendTypes::Saturation => hsv(lower, upper, |lower, upper| {
+                Hsv::new(lower.hue, upper.saturation, lower.value)
+            }),
+            ColorBlendTypes::Value => hsv(lower, upper, |lower, upper| {
+                Hsv::new(lower.hue, lower.saturation, upper.value)
+            }),
+        }
+    }
+}
+
+/// Blend every channel on its own.
+/// Channels are clamped from 0 to 1 first since the formulas expect it.
+fn separable(lower: Srgb<f64>, upper: Srgb<f64>, f: impl Fn(f64, f64) -> f64) -> Srgb<f64> {
+    let f = |a: f64, b: f64| f(a.clamp(0.0, 1.0), b.clamp(0.0, 1.0));
+    Srgb::new(
+        f(lower.red, upper.red),
+        f(lower.green, upper.green),
+        f(lower.blue, upper.blue),
+    )
+}
+
+/// Blend in HSV space.
+fn hsv(
+    lower: Srgb<f64>,
+    upper: Srgb<f64>,
+    f: impl Fn(HsvColor, HsvColor) -> HsvColor,
+) -> Srgb<f64> {
+    let lower: HsvColor = clamp(lower).into_color();
+    let upper: HsvColor = clamp(upper).into_color();
+    f(lower, upper).into_color()
+}
+
+fn clamp(color: Srgb<f64>) -> Srgb<f64> {
+    Srgb::new(
+        color.red.clamp(0.0, 1.0),
+        color.green.clamp(0.0, 1.0),
+        color.blue.clamp(0.0, 1.0),
+    )
+}
+
+fn hard_light(a: f64, b: f64) -> f64 {
+    if b <= 0.5 {
+        2.0 * a * b
+    } else {
+        1.0 - 2.0 * (1.0 - a) * (1.0 - b)
+    }
+}
+
+/// Soft light as in the W3C compositing spec.
+fn soft_light(a: f64, b: f64) -> f64 {
+    if b <= 0.5 {
+        a - (1.0 - 2.0 * b) * a * (1.0 - a)
+    } else {
+        let d = if a <= 0.25 {
+            ((16.0 * a - 12.0) * a + 4.0) * a
+        } else {
+            a.sqrt()
+        };
+        a + (2.0 * b - 1.0) * (d - a)
+    }
+}
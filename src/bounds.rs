@@ -2,7 +2,7 @@ use nalgebra::Vector2;
 
 /// A rectangle which can be used as a collider or as a mesh for effects.
 /// Used in flappy bird as such.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Bounds {
     pub position: Vector2<f64>,
     pub size: Vector2<f64>,
@@ -23,6 +23,13 @@ impl Bounds {
             && self.position.y + self.size.y > other.position.y
     }
 
+    /// Distance from the point to the closest edge. 0 when the point is inside.
+    pub fn distance(&self, point: Vector2<f64>) -> f64 {
+        let dx = (self.position.x - point.x).max(point.x - (self.position.x + self.size.x));
+        let dy = (self.position.y - point.y).max(point.y - (self.position.y + self.size.y));
+        Vector2::new(dx.max(0.0), dy.max(0.0)).norm()
+    }
+
     pub fn center(&self) -> Vector2<f64> {
         self.position + self.size * 0.5
     }
@@ -0,0 +1,77 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering::Relaxed},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Source of time for the runtime, its timers and tweeners.
+/// A manual clock only moves when it's advanced which makes animations reproducible.
+/// Clones share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct Clock {
+    manual: Option<ManualTime>,
+}
+
+#[derive(Debug, Clone)]
+struct ManualTime {
+    base: Instant,
+    offset_ns: Arc<AtomicU64>,
+}
+
+impl Clock {
+    /// Clock that follows real time.
+    pub fn real() -> Self {
+        Self { manual: None }
+    }
+
+    /// Clock that stays still until it's advanced.
+    pub fn manual() -> Self {
+        Self {
+            manual: Some(ManualTime {
+                base: Instant::now(),
+                offset_ns: Arc::new(0.into()),
+            }),
+        }
+    }
+
+    /// Whether this clock only moves when advanced.
+    pub fn is_manual(&self) -> bool {
+        self.manual.is_some()
+    }
+
+    /// Current time of the clock.
+    pub fn now(&self) -> Instant {
+        match &self.manual {
+            Some(manual) => manual.base + Duration::from_nanos(manual.offset_ns.load(Relaxed)),
+            None => Instant::now(),
+        }
+    }
+
+    /// Time elapsed since the given instant according to this clock.
+    pub fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+
+    /// Move a manual clock forward.
+    /// Does nothing on a real clock.
+    pub fn advance(&self, duration: Duration) {
+        if let Some(manual) = &self.manual {
+            manual
+                .offset_ns
+                .fetch_add(duration.as_nanos() as u64, Relaxed);
+        }
+    }
+
+    /// Sleep for the duration.
+    /// A manual clock is advanced instead of sleeping.
+    pub fn sleep(&self, duration: Duration) {
+        if self.is_manual() {
+            self.advance(duration);
+        } else {
+            thread::sleep(duration);
+        }
+    }
+}
@@ -1,10 +1,151 @@
+use std::{cell::RefCell, rc::Rc};
+
 use nalgebra::Vector2;
 use palette::Srgb;
 
+use crate::key::Key;
+
 /// Main trait for all kinds of effects.
 /// Mostly stateless since this doesn't allow mutation.
 /// However with some extra functions this can have state, like the ripple effect.
 pub trait Effect {
     /// Get color of the point based on space and time.
     fn color(&self, time: f64, pos_norm: Vector2<f64>) -> Srgb<f64>;
+
+    /// Get alpha of the point based on space and time.
+    /// Used as `Key::alpha` when the effect is bound to a layer.
+    fn alpha(&self, _time: f64, _pos_norm: Vector2<f64>) -> f64 {
+        1.0
+    }
+
+    /// Color of a whole key, for effects that color keys instead of points.
+    /// When bound to a layer this is used instead of sampling `color` if it returns Some.
+    fn key_color(&self, _time: f64, _key: &Key) -> Option<Srgb<f64>> {
+        None
+    }
+
+    /// Update the state of the effect.
+    /// Called by the runtime on every loop for effects bound to a layer.
+    fn update(&mut self, _time: f64) {}
+}
+
+/// Lets you keep a handle to an effect that is bound to a layer.
+impl<E: Effect + ?Sized> Effect for Rc<RefCell<E>> {
+    fn color(&self, time: f64, pos_norm: Vector2<f64>) -> Srgb<f64> {
+        self.borrow().color(time, pos_norm)
+    }
+
+    fn alpha(&self, time: f64, pos_norm: Vector2<f64>) -> f64 {
+        self.borrow().alpha(time, pos_norm)
+    }
+
+    fn key_color(&self, time: f64, key: &Key) -> Option<Srgb<f64>> {
+        self.borrow().key_color(time, key)
+    }
+
+    fn update(&mut self, time: f64) {
+        self.borrow_mut().update(time);
+    }
+}
+
+/// Which position of a key is given to an effect.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Coordinates {
+    /// `Key::pos_norm`
+    Normalized,
+    /// `Key::pos_norm_aspect`
+    #[default]
+    AspectCorrected,
+}
+
+impl Coordinates {
+    /// Position of the key in these coordinates.
+    pub fn position(&self, key: &Key) -> Vector2<f64> {
+        match self {
+            Coordinates::Normalized => key.pos_norm,
+            Coordinates::AspectCorrected => key.pos_norm_aspect,
+        }
+    }
+
+    /// Size of the key in these coordinates.
+    pub fn size(&self, key: &Key) -> Vector2<f64> {
+        match self {
+            Coordinates::Normalized => key.size_norm,
+            Coordinates::AspectCorrected => key.size_norm_aspect,
+        }
+    }
+
+    /// `samples` x `samples` points spread evenly over the area of the key in these coordinates.
+    /// A single sample is the centre of the key.
+    pub fn sample_points(&self, key: &Key, samples: u32) -> Vec<Vector2<f64>> {
+        let centre = self.position(key);
+        let samples = samples.max(1);
+        // From key units to these coordinates
+        let size = self.size(key);
+        let scale = Vector2::new(
+            if key.size.x > 0.0 {
+                size.x / key.size.x
+            } else {
+                0.0
+            },
+            if key.size.y > 0.0 {
+                size.y / key.size.y
+            } else {
+                0.0
+            },
+        );
+        let (sin, cos) = key.rotation.to_radians().sin_cos();
+
+        let mut points = Vec::with_capacity((samples * samples) as usize);
+        for i in 0..samples {
+            for j in 0..samples {
+                // Centre of the cell, from -0.5 to 0.5
+                let u = (i as f64 + 0.5) / samples as f64 - 0.5;
+                let v = (j as f64 + 0.5) / samples as f64 - 0.5;
+                let offset = Vector2::new(u * key.size.x, v * key.size.y);
+                let rotated = Vector2::new(
+                    offset.x * cos - offset.y * sin,
+                    offset.x * sin + offset.y * cos,
+                );
+                points.push(centre + rotated.component_mul(&scale));
+            }
+        }
+        points
+    }
+}
+
+/// Effect that the runtime renders to a layer.
+pub(crate) struct EffectBinding {
+    pub effect: Box<dyn Effect>,
+    pub coordinates: Coordinates,
+    /// Samples per axis over the area of a key
+    pub supersampling: u32,
+}
+
+impl EffectBinding {
+    /// Color and alpha of the key.
+    /// The key color of the effect if it has one, otherwise the average of every sample point
+    /// weighted by their alpha.
+    pub fn evaluate(&self, time: f64, key: &Key) -> (Srgb<f64>, f64) {
+        if let Some(color) = self.effect.key_color(time, key) {
+            let alpha = self.effect.alpha(time, self.coordinates.position(key));
+            return (color, alpha);
+        }
+
+        let points = self.coordinates.sample_points(key, self.supersampling);
+        let mut color = Srgb::new(0.0, 0.0, 0.0);
+        let mut alpha = 0.0;
+        for point in points.iter().copied() {
+            let a = self.effect.alpha(time, point);
+            color += self.effect.color(time, point) * a;
+            alpha += a;
+        }
+
+        let color = if alpha > 0.0 {
+            color / alpha
+        } else {
+            self.effect.color(time, self.coordinates.position(key))
+        };
+        (color, alpha / points.len() as f64)
+    }
 }
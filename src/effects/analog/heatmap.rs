@@ -0,0 +1,383 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+};
+
+use nalgebra::Vector2;
+use palette::{Mix, Srgb};
+
+use crate::{
+    effect::{Coordinates, Effect},
+    effects::analog::KeyFilter,
+    key::Key,
+    keyboard::{DeltaWatcher, KeyEvent, KeyEventKind, current_layout},
+};
+
+/// How often a heatmap with a file saves it in seconds
+const AUTOSAVE_INTERVAL: f64 = 30.0;
+
+/// Usage of a single key.
+/// Fractional once decay is used.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KeyUsage {
+    pub presses: f64,
+    /// How far the key has moved up and down in full travels
+    pub travel: f64,
+}
+
+/// What the heatmap colors keys by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HeatMetric {
+    #[default]
+    Presses,
+    Travel,
+}
+
+/// How usage fades over time.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Decay {
+    /// Keep everything forever
+    #[default]
+    None,
+    /// Usage halves in this many seconds while the heatmap is running.
+    /// Half lives that aren't above 0 don't decay.
+    HalfLife(f64),
+}
+
+/// Colors keys by how much they are used.
+/// Counts presses from the key events of the delta watcher and travel from its keys.
+///
+/// With `with_file` the usage is loaded from and saved to a CSV file, so it keeps
+/// accumulating between runs. `to_csv` and `to_json` export it for analysis.
+/// The file has the labels of the keys, so usage of another layout isn't loaded.
+pub struct Heatmap {
+    /// In case you want to clone this.
+    pub delta_watcher: DeltaWatcher,
+    /// Colors from the least used to the most used key
+    pub gradient: Vec<Srgb<f64>>,
+    pub metric: HeatMetric,
+    pub decay: Decay,
+    /// Which position of the keys `color` gets.
+    /// Only used when calling `color` directly, a bound heatmap colors keys by their matrix position.
+    pub coordinates: Coordinates,
+    /// Keys that are counted
+    pub filter: KeyFilter,
+    usage: BTreeMap<(u8, u8), KeyUsage>,
+    events: Receiver<KeyEvent>,
+    /// Last seen total travel of every scan code
+    travel: HashMap<u16, f64>,
+    /// Heat of every key from 0 to 1
+    heat: HashMap<(u8, u8), f64>,
+    file: Option<PathBuf>,
+    last_update: Option<f64>,
+    last_save: f64,
+}
+
+impl Heatmap {
+    pub fn new(delta_watcher: DeltaWatcher) -> Self {
+        Self {
+            events: delta_watcher.subscribe(),
+            travel: delta_watcher
+                .keys
+                .iter()
+                .map(|key| (key.scan_code, key.total_travel()))
+                .collect(),
+            delta_watcher,
+            gradient: vec![
+                Srgb::new(0.0, 0.0, 0.0),
+                Srgb::new(0.0, 0.0, 1.0),
+                Srgb::new(0.0, 1.0, 0.0),
+                Srgb::new(1.0, 1.0, 0.0),
+                Srgb::new(1.0, 0.0, 0.0),
+            ],
+            metric: HeatMetric::default(),
+            decay: Decay::default(),
+            coordinates: Coordinates::default(),
+            filter: KeyFilter::All,
+            usage: BTreeMap::new(),
+            heat: HashMap::new(),
+            file: None,
+            last_update: None,
+            last_save: 0.0,
+        }
+    }
+
+    /// Heatmap that keeps its usage in the file.
+    /// Existing usage is loaded from it and it is saved every 30 seconds and when dropped.
+    /// If the file can't be loaded, it is left alone and nothing is saved.
+    pub fn with_file<P: AsRef<Path>>(delta_watcher: DeltaWatcher, path: P) -> Self {
+        let mut heatmap = Self::new(delta_watcher);
+        if path.as_ref().exists()
+            && let Err(err) = heatmap.load(&path)
+        {
+            println!("Could not load heatmap, not saving to it: {err}");
+            return heatmap;
+        }
+        heatmap.file = Some(path.as_ref().to_path_buf());
+        heatmap
+    }
+
+    /// Usage of every key that has been used.
+    pub fn usage(&self) -> &BTreeMap<(u8, u8), KeyUsage> {
+        &self.usage
+    }
+
+    /// Usage of the key at the matrix position.
+    pub fn key_usage(&self, key: (u8, u8)) -> KeyUsage {
+        self.usage.get(&key).copied().unwrap_or_default()
+    }
+
+    /// Forget all usage.
+    pub fn clear(&mut self) {
+        self.usage.clear();
+        self.heat.clear();
+    }
+
+    /// Add usage from the CSV file to the current usage.
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        self.parse_csv(&text)
+    }
+
+    /// Save the usage to a CSV file `load` can read.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        std::fs::write(path, self.to_csv()).map_err(|err| err.to_string())
+    }
+
+    /// Save the usage as JSON.
+    pub fn export_json<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        std::fs::write(path, self.to_json()).map_err(|err| err.to_string())
+    }
+
+    /// Add usage from CSV text in the format of `to_csv`.
+    /// Fails without adding anything if a label doesn't match the current layout.
+    pub fn parse_csv(&mut self, text: &str) -> Result<(), String> {
+        let layout = current_layout();
+        let mut usage = Vec::new();
+        // Skip the header
+        for (i, line) in text.lines().enumerate().skip(1) {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: &str| format!("Line {}: {msg}", i + 1);
+
+            let fields = split_csv(line).map_err(|err| error(&err))?;
+            let [row, column, label, presses, travel] = &fields[..] else {
+                return Err(error("Expected row,column,label,presses,travel"));
+            };
+            let int = |s: &str| s.parse::<u8>().map_err(|err| error(&err.to_string()));
+            let float = |s: &str| s.parse::<f64>().map_err(|err| error(&err.to_string()));
+            let key = (int(row)?, int(column)?);
+            let layout_label = layout
+                .key(key)
+                .map(|key| key.label.as_str())
+                .unwrap_or_default();
+            if !label.is_empty() && label != layout_label {
+                return Err(error(&format!(
+                    "Key {key:?} is `{label}` in the file but `{layout_label}` on the layout"
+                )));
+            }
+            usage.push((
+                key,
+                KeyUsage {
+                    presses: float(presses)?,
+                    travel: float(travel)?,
+                },
+            ));
+        }
+
+        for (key, added) in usage {
+            let usage = self.usage.entry(key).or_default();
+            usage.presses += added.presses;
+            usage.travel += added.travel;
+        }
+        self.update_heat();
+        Ok(())
+    }
+
+    /// Usage as CSV with the columns row, column, label, presses and travel.
+    pub fn to_csv(&self) -> String {
+        let layout = current_layout();
+        let mut text = String::from("row,column,label,presses,travel\n");
+        for (key, usage) in self.usage.iter() {
+            let label = layout
+                .key(*key)
+                .map(|key| key.label.as_str())
+                .unwrap_or_default();
+            text += &format!(
+                "{},{},{},{},{}\n",
+                key.0,
+                key.1,
+                quote_csv(label),
+                usage.presses,
+                usage.travel
+            );
+        }
+        text
+    }
+
+    /// Usage as a JSON array of objects with row, column, label, presses and travel.
+    pub fn to_json(&self) -> String {
+        let layout = current_layout();
+        let keys = self
+            .usage
+            .iter()
+            .map(|(key, usage)| {
+                let label = layout
+                    .key(*key)
+                    .map(|key| key.label.as_str())
+                    .unwrap_or_default();
+                format!(
+                    "  {{\"row\": {}, \"column\": {}, \"label\": \"{}\", \"presses\": {}, \"travel\": {}}}",
+                    key.0,
+                    key.1,
+                    label.replace('\\', "\\\\").replace('"', "\\\""),
+                    usage.presses,
+                    usage.travel
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("[\n{}\n]\n", keys.join(",\n"))
+    }
+
+    /// Recalculate the heat of every key relative to the most used one.
+    fn update_heat(&mut self) {
+        let value = |usage: &KeyUsage| match self.metric {
+            HeatMetric::Presses => usage.presses,
+            HeatMetric::Travel => usage.travel,
+        };
+        let max = self.usage.values().map(value).fold(0.0, f64::max);
+        self.heat = self
+            .usage
+            .iter()
+            .map(|(key, usage)| {
+                let heat = if max > 0.0 { value(usage) / max } else { 0.0 };
+                (*key, heat)
+            })
+            .collect();
+    }
+
+    /// Color of the heat from 0 to 1 on the gradient.
+    fn gradient_color(&self, heat: f64) -> Srgb<f64> {
+        match self.gradient.len() {
+            0 => Srgb::new(0.0, 0.0, 0.0),
+            1 => self.gradient[0],
+            len => {
+                let position = heat.clamp(0.0, 1.0) * (len - 1) as f64;
+                let i = (position.floor() as usize).min(len - 2);
+                self.gradient[i].mix(self.gradient[i + 1], position - i as f64)
+            }
+        }
+    }
+}
+
+/// The field quoted if it has to be, as in RFC 4180.
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Fields of a CSV line with the quotes of `quote_csv` removed.
+fn split_csv(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Quote is never closed".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+impl Effect for Heatmap {
+    fn color(&self, _time: f64, pos_norm: Vector2<f64>) -> Srgb<f64> {
+        // Color of the nearest key
+        let key = self
+            .delta_watcher
+            .mat_keys
+            .keys()
+            .min_by(|a, b| {
+                let a = self.coordinates.position(a).metric_distance(&pos_norm);
+                let b = self.coordinates.position(b).metric_distance(&pos_norm);
+                a.total_cmp(&b)
+            })
+            .map(|key| key.key);
+        let heat = key
+            .and_then(|key| self.heat.get(&key))
+            .copied()
+            .unwrap_or_default();
+        self.gradient_color(heat)
+    }
+
+    fn key_color(&self, _time: f64, key: &Key) -> Option<Srgb<f64>> {
+        let heat = self.heat.get(&key.key).copied().unwrap_or_default();
+        Some(self.gradient_color(heat))
+    }
+
+    fn update(&mut self, time: f64) {
+        let dt = time - self.last_update.unwrap_or(time);
+        self.last_update = Some(time);
+
+        if let Decay::HalfLife(half_life) = self.decay
+            && half_life > 0.0
+            && dt > 0.0
+        {
+            let factor = 0.5f64.powf(dt / half_life);
+            for usage in self.usage.values_mut() {
+                usage.presses *= factor;
+                usage.travel *= factor;
+            }
+        }
+
+        for event in self.events.try_iter() {
+            if event.kind == KeyEventKind::Pressed && self.filter.allows(event.key) {
+                self.usage.entry(event.key).or_default().presses += 1.0;
+            }
+        }
+        for key in self.delta_watcher.keys.iter() {
+            let total = key.total_travel();
+            let last = self.travel.insert(key.scan_code, total).unwrap_or(total);
+            if total > last && self.filter.allows(key.key) {
+                self.usage.entry(key.key).or_default().travel += total - last;
+            }
+        }
+        self.update_heat();
+
+        if let Some(file) = &self.file
+            && time - self.last_save >= AUTOSAVE_INTERVAL
+        {
+            self.last_save = time;
+            if let Err(err) = self.save(file) {
+                println!("Could not save heatmap: {err}");
+            }
+        }
+    }
+}
+
+impl Drop for Heatmap {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file
+            && let Err(err) = self.save(file)
+        {
+            println!("Could not save heatmap: {err}");
+        }
+    }
+}
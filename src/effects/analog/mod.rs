@@ -1,11 +1,15 @@
 mod area;
+mod heatmap;
 mod local_press_brightness;
 mod velocity;
 
 pub use area::*;
+pub use heatmap::*;
 pub use local_press_brightness::*;
 pub use velocity::*;
 
+use crate::keyboard::{KeyGroup, key_positions};
+
 /// Change what keys effect and what not.
 #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub enum KeyFilter {
@@ -17,3 +21,34 @@ pub enum KeyFilter {
     /// Exclude these keys
     Excluded(Vec<(u8, u8)>),
 }
+
+impl KeyFilter {
+    /// Whether the filter lets the key through.
+    pub fn allows(&self, key: (u8, u8)) -> bool {
+        match self {
+            KeyFilter::All => true,
+            KeyFilter::Included(items) => items.contains(&key),
+            KeyFilter::Excluded(items) => !items.contains(&key),
+        }
+    }
+
+    /// Include the keys of the group on the current layout.
+    pub fn include(group: KeyGroup) -> Self {
+        Self::Included(group.positions())
+    }
+
+    /// Exclude the keys of the group on the current layout.
+    pub fn exclude(group: KeyGroup) -> Self {
+        Self::Excluded(group.positions())
+    }
+
+    /// Include the keys with the labels on the current layout.
+    pub fn include_labels(labels: &[&str]) -> Self {
+        Self::Included(key_positions(labels))
+    }
+
+    /// Exclude the keys with the labels on the current layout.
+    pub fn exclude_labels(labels: &[&str]) -> Self {
+        Self::Excluded(key_positions(labels))
+    }
+}
@@ -1,6 +1,3 @@
-use std::sync::atomic::Ordering::Relaxed;
-
-use lerp::num_traits::Signed;
 use palette::{Srgb, num::ClampAssign};
 
 use crate::{effect::Effect, effects::analog::KeyFilter, keyboard::DeltaWatcher};
@@ -25,6 +22,7 @@ pub struct Velocity {
     /// Area around the key to light up
     pub area: f64,
     /// Intensity factor of the light that happens when a key has velocity.
+    /// Multiplied with the velocity in full travels per second.
     pub intensity: f64,
     /// What should trigger the velocity
     pub velocity_type: VelocityType,
@@ -69,14 +67,14 @@ impl Effect for Velocity {
                 }
             }
             if mat_key.pos_norm.metric_distance(&pos_norm) < self.area {
-                let delta = key.delta_average.load(Relaxed);
-                let delta = match self.velocity_type {
-                    VelocityType::Both => delta.abs(),
-                    VelocityType::Up if delta.is_negative() => delta.abs(),
-                    VelocityType::Down if delta.is_positive() => delta,
-                    _ => 0,
+                let velocity = key.velocity();
+                let velocity = match self.velocity_type {
+                    VelocityType::Both => velocity.abs(),
+                    VelocityType::Up if velocity < 0.0 => -velocity,
+                    VelocityType::Down if velocity > 0.0 => velocity,
+                    _ => 0.0,
                 };
-                intensity += self.intensity * delta as f64;
+                intensity += self.intensity * velocity;
             }
         }
 
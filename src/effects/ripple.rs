@@ -21,6 +21,9 @@ pub struct Ripple {
     /// true means that this darkens when multiplied with the underlying layer.
     /// false means that this brightens when multiplied with the underlying layer.
     pub inverted: bool,
+    /// Output the ripple as alpha over a solid color instead of as brightness.
+    /// Lets the ripple fade softly onto the layers below.
+    pub fade: bool,
 }
 
 impl Ripple {
@@ -40,31 +43,16 @@ impl Ripple {
             decay,
             width,
             inverted,
+            fade: false,
         }
     }
 
-    pub fn update(&mut self, time: f64) {
-        for (key, mat_key) in self.delta_watcher.get_pressed_keys_mat_keys() {
-            if key.just_pressed() {
-                self.ripples.push(RippleEvent {
-                    origin: mat_key.pos_norm_aspect,
-                    start_time: time,
-                });
-            }
-        }
-
-        self.ripples
-            .retain(|ripple| time - ripple.start_time < self.max_lifetime);
-    }
-
     fn wave(&self, x: f64) -> f64 {
         let v = 1.0 - (x.abs() / self.width);
         v.clamp(0.0, 1.0)
     }
-}
 
-impl Effect for Ripple {
-    fn color(&self, time: f64, pos_norm: nalgebra::Vector2<f64>) -> palette::Srgb<f64> {
+    fn intensity(&self, time: f64, pos_norm: Vector2<f64>) -> f64 {
         let mut intensity = 0.0;
 
         for ripple in self.ripples.iter() {
@@ -81,13 +69,51 @@ impl Effect for Ripple {
             intensity += wave_strength * decay;
         }
 
-        intensity = intensity.clamp(0.0, 1.0);
+        intensity.clamp(0.0, 1.0)
+    }
+
+    /// Starts ripples from keys that were just pressed.
+    /// The runtime calls this for bound effects, call it yourself otherwise.
+    pub fn update(&mut self, time: f64) {
+        for (key, mat_key) in self.delta_watcher.get_pressed_keys_mat_keys() {
+            if key.just_pressed() {
+                self.ripples.push(RippleEvent {
+                    origin: mat_key.pos_norm_aspect,
+                    start_time: time,
+                });
+            }
+        }
+
+        self.ripples
+            .retain(|ripple| time - ripple.start_time < self.max_lifetime);
+    }
+}
+
+impl Effect for Ripple {
+    fn color(&self, time: f64, pos_norm: nalgebra::Vector2<f64>) -> palette::Srgb<f64> {
+        let mut intensity = if self.fade {
+            1.0
+        } else {
+            self.intensity(time, pos_norm)
+        };
         if self.inverted {
             intensity = 1.0 - intensity;
         }
 
         Srgb::new(intensity, intensity, intensity)
     }
+
+    fn alpha(&self, time: f64, pos_norm: Vector2<f64>) -> f64 {
+        if self.fade {
+            self.intensity(time, pos_norm)
+        } else {
+            1.0
+        }
+    }
+
+    fn update(&mut self, time: f64) {
+        Ripple::update(self, time);
+    }
 }
 
 struct RippleEvent {
@@ -1,28 +1,57 @@
 use nalgebra::Vector2;
 use palette::Srgb;
 
-#[derive(Default, Clone, Copy, Debug)]
+use crate::output::OutputTransfer;
+
+#[derive(Clone, Copy, Debug)]
 pub struct Key {
     pub key: (u8, u8),
-    /// A value from (0, 0) to (4, 14) representing the physical key position
+    /// Physical key position in key units, see `Layout`.
+    /// On the 60HE a value from (0, 0) to (4, 14)
     pub physical_position: Vector2<f64>,
     /// Normalized position based on physical_position
     pub pos_norm: Vector2<f64>,
     /// Normalized and aspect ratio corrected position of this key.
     /// x values range over 1
     pub pos_norm_aspect: Vector2<f64>,
+    /// Width and height of the key in key units
+    pub size: Vector2<f64>,
+    /// Width and height of the key in the same units as pos_norm
+    pub size_norm: Vector2<f64>,
+    /// Width and height of the key in the same units as pos_norm_aspect
+    pub size_norm_aspect: Vector2<f64>,
+    /// Rotation of the key around its centre in degrees
+    pub rotation: f64,
     pub color: Srgb<f64>,
+    /// Coverage of this key from 0 to 1.
+    /// The blended color is composited over the layers below with this, 1 being fully opaque.
+    pub alpha: f64,
     pub color_blend_type: ColorBlendTypes,
 }
 
+impl Default for Key {
+    fn default() -> Self {
+        Self {
+            key: (0, 0),
+            physical_position: Vector2::zeros(),
+            pos_norm: Vector2::zeros(),
+            pos_norm_aspect: Vector2::zeros(),
+            size: Vector2::new(1.0, 1.0),
+            size_norm: Vector2::zeros(),
+            size_norm_aspect: Vector2::zeros(),
+            rotation: 0.0,
+            color: Srgb::default(),
+            alpha: 1.0,
+            color_blend_type: ColorBlendTypes::default(),
+        }
+    }
+}
+
 impl Key {
+    /// Color with 8 bits per channel, clamped to the valid range.
+    /// The runtime uses its own `OutputTransfer` instead of this.
     pub fn colors(&self) -> (u8, u8, u8) {
-        let (r, g, b) = self.color.into_components();
-        (
-            (r * 255.0).round() as u8,
-            (g * 255.0).round() as u8,
-            (b * 255.0).round() as u8,
-        )
+        OutputTransfer::default().to_rgb8(self.color)
     }
 }
 
@@ -42,9 +71,37 @@ pub enum ColorBlendTypes {
     /// with the second value.
     /// Then adds the two layers together.
     /// This gives good alpha layering.
+    /// For plain transparency `Key::alpha` with `Mask` is simpler.
     AlphaBlend(f64, f64),
     /// The render color is completely ignored and this key's color is used instead.
     Mask,
     /// This key is effectively ignored.
     Nothing,
+    /// Inverted colors multiplied and inverted again. Always brightens.
+    Screen,
+    /// Multiplies dark parts and screens bright parts of the render color.
+    Overlay,
+    /// Overlay with the layers swapped.
+    HardLight,
+    /// Softer version of hard light.
+    SoftLight,
+    /// Brighter of the two colors per channel.
+    Lighten,
+    /// Darker of the two colors per channel.
+    Darken,
+    /// Absolute difference of the two colors per channel.
+    Difference,
+    /// Brightens the render color based on this key's color.
+    ColorDodge,
+    /// Darkens the render color based on this key's color.
+    ColorBurn,
+    /// Replaces the hue of the render color with the hue of this key.
+    Hue,
+    /// Replaces the saturation of the render color with the saturation of this key.
+    Saturation,
+    /// Replaces the value of the render color with the value of this key.
+    Value,
+    /// Blend mode registered to the runtime with this id.
+    /// The key is ignored if there is no such blend mode.
+    Custom(u32),
 }
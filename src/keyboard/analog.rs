@@ -0,0 +1,194 @@
+use std::{
+    ffi::{c_float, c_int, c_uint, c_ushort},
+    fs, io,
+    path::Path,
+    sync::mpsc::{Receiver, Sender, TryRecvError, channel},
+};
+
+use wooting_analog_wrapper::{
+    ffi::{
+        wooting_analog_initialise, wooting_analog_read_full_buffer,
+        wooting_analog_set_keycode_mode, wooting_analog_uninitialise,
+    },
+    is_initialised,
+};
+
+use crate::keyboard::{KeycodeType, SCAN_CODE_LEN};
+
+/// Where the delta watcher reads raw analog data from.
+/// A sample is a scan code and how far down the key is pressed. (from 0 to 1)
+pub trait AnalogSource: Send {
+    /// Called once before the first read.
+    /// An error stops the delta watcher.
+    fn init(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+    /// Read every key that is pressed down into samples.
+    /// Keys released since the last read should be included once with a distance of 0.
+    /// An error stops the delta watcher.
+    fn read(&mut self, samples: &mut Vec<(u16, f32)>) -> Result<(), String>;
+    /// Called once when the delta watcher exits.
+    fn close(&mut self) {}
+}
+
+/// Reads analog data from the Wooting analog SDK.
+#[derive(Debug)]
+pub struct WootingAnalog {
+    code_buffer: Vec<c_ushort>,
+    analog_buffer: Vec<c_float>,
+    keycode_type: KeycodeType,
+}
+
+impl WootingAnalog {
+    /// Reads ScanCode1 codes.
+    pub fn new() -> Self {
+        Self::with_keycode_type(KeycodeType::ScanCode1)
+    }
+
+    /// Reads the given kind of key codes.
+    pub fn with_keycode_type(keycode_type: KeycodeType) -> Self {
+        Self {
+            code_buffer: vec![0; SCAN_CODE_LEN],
+            analog_buffer: vec![0.0; SCAN_CODE_LEN],
+            keycode_type,
+        }
+    }
+}
+
+impl Default for WootingAnalog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnalogSource for WootingAnalog {
+    fn init(&mut self) -> Result<(), String> {
+        unsafe { wooting_analog_initialise() };
+        if !is_initialised() {
+            return Err(
+                "Wooting analog SDK is not initialised, no deltas will be provided".to_string(),
+            );
+        }
+        let mode = match self.keycode_type {
+            KeycodeType::ScanCode1 => wooting_analog_wrapper::KeycodeType::ScanCode1,
+            KeycodeType::Hid => wooting_analog_wrapper::KeycodeType::HID,
+        };
+        unsafe { wooting_analog_set_keycode_mode(mode) };
+        Ok(())
+    }
+
+    fn read(&mut self, samples: &mut Vec<(u16, f32)>) -> Result<(), String> {
+        let result: c_int = unsafe {
+            wooting_analog_read_full_buffer(
+                self.code_buffer.as_mut_ptr(),
+                self.analog_buffer.as_mut_ptr(),
+                self.code_buffer.len() as c_uint,
+            )
+        };
+
+        if result < 0 {
+            return Err(result.to_string());
+        }
+
+        samples.extend(
+            self.code_buffer[0..result as usize]
+                .iter()
+                .copied()
+                .zip(self.analog_buffer[0..result as usize].iter().copied()),
+        );
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if is_initialised() {
+            unsafe { wooting_analog_uninitialise() };
+        }
+    }
+}
+
+/// Replays frames of samples from a channel or a file.
+/// Every frame only lists keys that are pressed down, the rest are released.
+/// The latest frame is repeated until a new one is received.
+#[derive(Debug)]
+pub struct ReplaySource {
+    frames: Receiver<Vec<(u16, f32)>>,
+    current: Vec<(u16, f32)>,
+    previous: Vec<u16>,
+}
+
+impl ReplaySource {
+    /// Replay frames received from the channel.
+    pub fn from_channel(frames: Receiver<Vec<(u16, f32)>>) -> Self {
+        Self {
+            frames,
+            current: Vec::new(),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Create a replay source and the sender to feed it with.
+    pub fn channel() -> (Sender<Vec<(u16, f32)>>, Self) {
+        let (sender, receiver) = channel();
+        (sender, Self::from_channel(receiver))
+    }
+
+    /// Replay frames from a text file.
+    /// Every line is a frame of `scan_code:distance` pairs separated by whitespace.
+    /// Empty lines are frames where nothing is pressed and lines starting with `#` are ignored.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Parse frames in the same format as [`ReplaySource::from_file`].
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (sender, source) = Self::channel();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let mut frame = Vec::new();
+            for sample in line.split_whitespace() {
+                let parsed = sample.split_once(':').and_then(|(code, distance)| {
+                    Some((code.parse::<u16>().ok()?, distance.parse::<f32>().ok()?))
+                });
+                match parsed {
+                    Some(sample) => frame.push(sample),
+                    None => return Err(format!("Invalid sample `{sample}` on line {}", i + 1)),
+                }
+            }
+            // The receiver is alive so this cannot fail
+            let _ = sender.send(frame);
+        }
+        Ok(source)
+    }
+}
+
+impl AnalogSource for ReplaySource {
+    fn read(&mut self, samples: &mut Vec<(u16, f32)>) -> Result<(), String> {
+        match self.frames.try_recv() {
+            Ok(frame) => self.current = frame,
+            Err(TryRecvError::Disconnected) => self.current.clear(),
+            Err(TryRecvError::Empty) => (),
+        }
+        samples.extend(self.current.iter().copied());
+        release_missing(&mut self.previous, samples);
+        Ok(())
+    }
+}
+
+/// Adds keys which were pressed previously but are missing from samples as released.
+/// Then remembers the currently pressed keys.
+pub(crate) fn release_missing(previous: &mut Vec<u16>, samples: &mut Vec<(u16, f32)>) {
+    for code in previous.iter() {
+        if !samples.iter().any(|(c, _)| c == code) {
+            samples.push((*code, 0.0));
+        }
+    }
+    *previous = samples
+        .iter()
+        .filter(|(_, distance)| *distance > 0.0)
+        .map(|(code, _)| *code)
+        .collect();
+}
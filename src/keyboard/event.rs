@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+/// How many events can wait in the queue before new ones are dropped
+pub const EVENT_QUEUE_LEN: usize = 1024;
+
+/// Something that happened to a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEventKind {
+    /// Went down to the actuation point, or moved down enough with rapid trigger
+    Pressed,
+    /// Came back up to the reset point, or moved up enough with rapid trigger
+    Released,
+    /// Has been pressed down for the hold time, sent once per press.
+    /// Contains how long the key has been held.
+    Held(Duration),
+    /// Moved past one of the distance thresholds of the delta watcher in either direction.
+    /// Check the distance of the event to know which way.
+    DistanceCrossed(u8),
+}
+
+/// Key event sent from the delta watcher to the runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub kind: KeyEventKind,
+    /// Position of the key in the rgb matrix
+    pub key: (u8, u8),
+    pub scan_code: u16,
+    /// Distance of the key when the event happened (from 0 to 255)
+    pub distance: u8,
+    /// Time since the delta watcher started
+    pub time: Duration,
+}
@@ -0,0 +1,85 @@
+use std::f64::consts::TAU;
+
+/// How far Wooting switches travel in millimetres
+pub const TRAVEL_MM: f64 = 4.0;
+
+/// Smoothing used on key velocity and acceleration.
+/// Both work in seconds so they behave the same with any scan rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VelocityFilter {
+    /// Raw values, noisy
+    None,
+    /// Exponential moving average
+    Ema {
+        /// How long in seconds it takes to move about two thirds of the way to a new value
+        time_constant: f64,
+    },
+    /// One euro filter, smooths slow movement a lot and fast movement little.
+    /// <https://gery.casiez.net/1euro/>
+    OneEuro {
+        /// Cutoff frequency in hertz when the value isn't changing
+        min_cutoff: f64,
+        /// How much the cutoff rises with the speed of change
+        beta: f64,
+        /// Cutoff frequency in hertz of the speed of change
+        derivative_cutoff: f64,
+    },
+}
+
+impl Default for VelocityFilter {
+    fn default() -> Self {
+        Self::Ema {
+            time_constant: 0.025,
+        }
+    }
+}
+
+impl VelocityFilter {
+    /// One euro filter with settings that work well for key presses.
+    pub fn one_euro() -> Self {
+        Self::OneEuro {
+            min_cutoff: 5.0,
+            beta: 0.05,
+            derivative_cutoff: 1.0,
+        }
+    }
+}
+
+/// State of a filtered value.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Filtered {
+    value: f64,
+    derivative: f64,
+}
+
+impl Filtered {
+    /// Filter a new raw value that came dt seconds after the last one.
+    /// If no time has passed, the last value is kept unless there is no filter.
+    pub(crate) fn update(&mut self, filter: VelocityFilter, raw: f64, dt: f64) -> f64 {
+        self.value = match filter {
+            VelocityFilter::None => raw,
+            VelocityFilter::Ema { time_constant } => {
+                let alpha = 1.0 - (-dt / time_constant.max(f64::EPSILON)).exp();
+                self.value + (raw - self.value) * alpha
+            }
+            VelocityFilter::OneEuro {
+                min_cutoff,
+                beta,
+                derivative_cutoff,
+            } if dt > 0.0 => {
+                let derivative = (raw - self.value) / dt;
+                self.derivative += (derivative - self.derivative) * alpha(derivative_cutoff, dt);
+                let cutoff = min_cutoff + beta * self.derivative.abs();
+                self.value + (raw - self.value) * alpha(cutoff, dt)
+            }
+            VelocityFilter::OneEuro { .. } => self.value,
+        };
+        self.value
+    }
+}
+
+/// Smoothing factor of a low pass filter.
+fn alpha(cutoff: f64, dt: f64) -> f64 {
+    let tau = 1.0 / (TAU * cutoff);
+    1.0 / (1.0 + tau / dt)
+}
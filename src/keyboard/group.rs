@@ -0,0 +1,124 @@
+use palette::Srgb;
+
+use crate::{
+    key::{ColorBlendTypes, Key},
+    keyboard::{KeyboardMatrix, current_layout},
+};
+
+/// Predefined groups of keys.
+/// Keys are matched by their label on the layout, so groups work on any layout that uses the
+/// same labels as the built-in ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyGroup {
+    /// A to Z and Å, Ä and Ö
+    Letters,
+    /// 0 to 9 on the number row
+    Digits,
+    /// Shifts, controls, alts, mods and fn
+    Modifiers,
+    Wasd,
+    Arrows,
+    /// F1 to F12
+    FunctionRow,
+    /// Keys of the numpad
+    Numpad,
+}
+
+impl KeyGroup {
+    /// Labels of the keys in this group.
+    pub fn labels(&self) -> &'static [&'static str] {
+        match self {
+            KeyGroup::Letters => &[
+                "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P",
+                "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z", "Å", "Ä", "Ö",
+            ],
+            KeyGroup::Digits => &["1", "2", "3", "4", "5", "6", "7", "8", "9", "0"],
+            KeyGroup::Modifiers => &[
+                "LeftShift",
+                "RightShift",
+                "LeftControl",
+                "RightControl",
+                "LeftAlt",
+                "AltGr",
+                "LeftMod",
+                "RightMod",
+                "Fn",
+            ],
+            KeyGroup::Wasd => &["W", "A", "S", "D"],
+            KeyGroup::Arrows => &["Up", "Left", "Down", "Right"],
+            KeyGroup::FunctionRow => &[
+                "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12",
+            ],
+            KeyGroup::Numpad => &[
+                "NumLock", "Num/", "Num*", "Num-", "Num7", "Num8", "Num9", "Num+", "Num4", "Num5",
+                "Num6", "Num1", "Num2", "Num3", "NumEnter", "Num0", "Num,",
+            ],
+        }
+    }
+
+    /// Matrix positions of the keys in this group on the current layout.
+    pub fn positions(&self) -> Vec<(u8, u8)> {
+        key_positions(self.labels())
+    }
+}
+
+/// Matrix position of the key with the label on the current layout.
+/// Use `key_positions` for several labels, this gets the layout every time.
+pub fn key_position(label: &str) -> Option<(u8, u8)> {
+    current_layout().key_by_label(label).map(|key| key.matrix)
+}
+
+/// Matrix positions of the keys with the labels on the current layout.
+/// Labels the layout doesn't have are skipped.
+pub fn key_positions(labels: &[&str]) -> Vec<(u8, u8)> {
+    current_layout().positions_of(labels)
+}
+
+/// Some keys of a matrix to change at once.
+/// Get one with `KeyboardMatrix::select`.
+#[derive(Debug)]
+pub struct Selection<'a> {
+    matrix: &'a mut KeyboardMatrix,
+    positions: Vec<(u8, u8)>,
+}
+
+impl<'a> Selection<'a> {
+    pub(crate) fn new(matrix: &'a mut KeyboardMatrix, positions: Vec<(u8, u8)>) -> Self {
+        Self { matrix, positions }
+    }
+
+    /// Matrix positions of the selected keys.
+    pub fn positions(&self) -> &[(u8, u8)] {
+        &self.positions
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Call the function on every selected key.
+    pub fn for_each(&mut self, mut f: impl FnMut(&mut Key)) -> &mut Self {
+        for position in self.positions.iter() {
+            if let Some(key) = self.matrix.get_mut(*position) {
+                f(key);
+            }
+        }
+        self
+    }
+
+    pub fn set_color(&mut self, color: Srgb<f64>) -> &mut Self {
+        self.for_each(|key| key.color = color)
+    }
+
+    pub fn set_alpha(&mut self, alpha: f64) -> &mut Self {
+        self.for_each(|key| key.alpha = alpha)
+    }
+
+    pub fn set_blend_type(&mut self, blend_type: ColorBlendTypes) -> &mut Self {
+        self.for_each(|key| key.color_blend_type = blend_type)
+    }
+}
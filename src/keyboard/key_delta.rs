@@ -1,25 +1,27 @@
 use std::{
-    ffi::{c_float, c_int, c_uint, c_ushort},
+    collections::{HashSet, VecDeque},
+    fmt::{self, Debug},
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicBool, AtomicI32, AtomicU8, AtomicU64, Ordering::Relaxed},
+        mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
     },
     thread::{self, sleep},
-    time::{Duration, Instant},
+    time::Duration,
 };
 
 use crate::{
+    clock::Clock,
     key::Key,
-    keyboard::{KeyboardMatrix, SCAN_CODE_LEN, SCAN_CODES, get_matrix, scan_code_to_matrix_pos},
-};
-use ringbuffer::{AllocRingBuffer, RingBuffer};
-use wooting_analog_wrapper::{
-    ffi::{
-        wooting_analog_initialise, wooting_analog_read_full_buffer,
-        wooting_analog_set_keycode_mode, wooting_analog_uninitialise,
+    keyboard::{
+        AnalogSource, EVENT_QUEUE_LEN, KeyEvent, KeyEventKind, KeyboardMatrix, SCAN_CODE_LEN,
+        TRAVEL_MM, VelocityFilter, WootingAnalog, current_translation,
+        filter::Filtered,
+        get_matrix,
+        keystroke::{DEFAULT_KEYSTROKE_HISTORY, Keystroke, StrokeTracker},
     },
-    is_initialised,
 };
+use ringbuffer::{AllocRingBuffer, RingBuffer};
 
 #[derive(Clone, Debug, Default)]
 pub struct KeyDelta {
@@ -29,13 +31,136 @@ pub struct KeyDelta {
     pub last_distance: Arc<AtomicU8>,
     pub delta: Arc<AtomicI32>,
     pub delta_average: Arc<AtomicI32>,
+    /// Smoothed velocity in travel per second as f64 bits, see `velocity`
+    pub velocity: Arc<AtomicU64>,
+    /// Smoothed acceleration in travel per second squared as f64 bits, see `acceleration`
+    pub acceleration: Arc<AtomicU64>,
+    /// Highest velocity of the keystroke as f64 bits, see `peak_velocity`
+    pub peak_velocity: Arc<AtomicU64>,
+    /// Last finished keystrokes, oldest first
+    pub keystrokes: Arc<Mutex<VecDeque<Keystroke>>>,
+    /// How far the key has moved up and down in total as f64 bits, see `total_travel`
+    pub total_travel: Arc<AtomicU64>,
     /// In what distance should the key be considered pressed down
     /// (from 0 to 255, 255 being completely pressed down)
     pub key_press_distance: Arc<AtomicU8>,
+    /// Distance the key has to come back up to before it is released
+    pub reset_distance: Arc<AtomicU8>,
+    /// How much the key has to travel to press or release it again with rapid trigger.
+    /// 0 turns rapid trigger off.
+    pub rapid_trigger: Arc<AtomicU8>,
+    /// Whether the key is actuated right now
+    pub pressed: Arc<AtomicBool>,
     pub just_pressed: Arc<AtomicBool>,
 }
 
+/// How far keys go before they are pressed and released (from 0 to 255).
+/// Like the actuation settings of Wooting keyboards.
+/// Keys use it `clamped`, so a key at rest is never pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Actuation {
+    /// Actuation point, the key is pressed when it goes down to this
+    pub press: u8,
+    /// Reset point, the key is released when it comes back up to this.
+    /// Keep it a bit above the actuation point so noise doesn't press the key again.
+    pub reset: u8,
+    /// With rapid trigger the key is pressed and released every time it travels this much
+    /// down or up while it is below the reset point.
+    pub rapid_trigger: Option<u8>,
+}
+
+impl Actuation {
+    /// Actuation point with the reset point a bit above it and no rapid trigger.
+    pub fn new(press: u8) -> Self {
+        Self {
+            press,
+            reset: press.saturating_sub(DEFAULT_HYSTERESIS),
+            rapid_trigger: None,
+        }
+    }
+
+    /// Turn rapid trigger on with the given sensitivity.
+    pub fn with_rapid_trigger(mut self, sensitivity: u8) -> Self {
+        self.rapid_trigger = Some(sensitivity);
+        self
+    }
+
+    /// Actuation point at least 1 and the reset point below it.
+    /// Otherwise a key could be pressed and released on the same scan.
+    pub fn clamped(self) -> Self {
+        let press = self.press.max(1);
+        Self {
+            press,
+            reset: self.reset.min(press - 1),
+            rapid_trigger: self.rapid_trigger.filter(|sensitivity| *sensitivity > 0),
+        }
+    }
+}
+
+/// How far above the actuation point the reset point is by default
+pub const DEFAULT_HYSTERESIS: u8 = 10;
+
 impl KeyDelta {
+    /// Velocity of the key in full travels per second.
+    /// Positive when the key is going down and negative when it is coming up.
+    pub fn velocity(&self) -> f64 {
+        f64::from_bits(self.velocity.load(Relaxed))
+    }
+
+    /// Velocity of the key in millimetres per second.
+    pub fn velocity_mm(&self) -> f64 {
+        self.velocity() * TRAVEL_MM
+    }
+
+    /// Acceleration of the key in full travels per second squared.
+    pub fn acceleration(&self) -> f64 {
+        f64::from_bits(self.acceleration.load(Relaxed))
+    }
+
+    /// Highest press velocity of the current keystroke in full travels per second.
+    /// Keeps the one of the last keystroke while the key is at rest.
+    pub fn peak_velocity(&self) -> f64 {
+        f64::from_bits(self.peak_velocity.load(Relaxed))
+    }
+
+    /// How far the key has moved up and down in total in full travels.
+    pub fn total_travel(&self) -> f64 {
+        f64::from_bits(self.total_travel.load(Relaxed))
+    }
+
+    /// Last finished keystrokes of the key, oldest first.
+    pub fn keystrokes(&self) -> Vec<Keystroke> {
+        self.keystrokes.lock().unwrap().iter().copied().collect()
+    }
+
+    /// The keystroke that finished last.
+    pub fn last_keystroke(&self) -> Option<Keystroke> {
+        self.keystrokes.lock().unwrap().back().copied()
+    }
+
+    /// Whether the key is actuated right now.
+    pub fn is_pressed(&self) -> bool {
+        self.pressed.load(Relaxed)
+    }
+
+    pub fn actuation(&self) -> Actuation {
+        Actuation {
+            press: self.key_press_distance.load(Relaxed),
+            reset: self.reset_distance.load(Relaxed),
+            rapid_trigger: Some(self.rapid_trigger.load(Relaxed)).filter(|v| *v > 0),
+        }
+    }
+
+    /// Change the actuation and reset points and rapid trigger of the key.
+    /// Invalid points are `clamped`.
+    pub fn set_actuation(&self, actuation: Actuation) {
+        let actuation = actuation.clamped();
+        self.key_press_distance.store(actuation.press, Relaxed);
+        self.reset_distance.store(actuation.reset, Relaxed);
+        self.rapid_trigger
+            .store(actuation.rapid_trigger.unwrap_or(0), Relaxed);
+    }
+
     /// Whether the key was pressed just now.
     pub fn just_pressed(&self) -> bool {
         self.just_pressed.load(Relaxed)
@@ -50,8 +175,11 @@ impl KeyDelta {
 
 #[derive(Clone, Debug)]
 pub struct DeltaWatcher {
-    /// Per key delta data and related
-    pub keys: [KeyDelta; SCAN_CODE_LEN],
+    /// Per key delta data and related.
+    /// There is one for every code of the translation, see `set_translation`.
+    pub keys: Vec<KeyDelta>,
+    /// Codes the source reported that are not in the translation
+    pub unmapped: Arc<Mutex<Vec<u16>>>,
     /// How much to wait in nanoseconds before scanning key states again
     pub scan_delay_ns: Arc<AtomicU64>,
     /// How many delta data should be in an average delta calculation
@@ -60,51 +188,208 @@ pub struct DeltaWatcher {
     pub exit: Arc<AtomicBool>,
     /// Whether the watcher should be in power save mode or not
     pub idle: Arc<AtomicBool>,
+    /// Distances (from 0 to 255) that send a `DistanceCrossed` event when a key moves past them
+    pub distance_thresholds: Arc<Mutex<Vec<u8>>>,
+    /// How long in nanoseconds a key has to be pressed down before it sends a `Held` event
+    pub hold_time_ns: Arc<AtomicU64>,
+    /// Smoothing of key velocity and acceleration
+    pub velocity_filter: Arc<Mutex<VelocityFilter>>,
+    /// How many of the last keystrokes every key keeps
+    pub keystroke_history: Arc<AtomicU64>,
+    /// How many key events have been dropped because a queue was full.
+    /// Counts the queue of `events` and the ones of subscribers.
+    pub dropped_events: Arc<AtomicU64>,
     pub mat_keys: KeyboardMatrix,
+    events: Arc<Mutex<Receiver<KeyEvent>>>,
+    event_sender: SyncSender<KeyEvent>,
+    subscribers: Arc<Mutex<Vec<SyncSender<KeyEvent>>>>,
+    polled: Option<Arc<Mutex<Polled>>>,
 }
 
 impl DeltaWatcher {
+    /// Creates a delta watcher which reads the Wooting analog SDK in its own thread.
     pub fn new(scan_delay: Duration, deltas_in_average: u64, key_press_distance: u8) -> Self {
-        let s_ret = Self {
-            keys: {
-                SCAN_CODES.map(|scan_code| KeyDelta {
-                    key: scan_code_to_matrix_pos(scan_code).expect("Dev error"),
+        Self::with_source(
+            WootingAnalog::with_keycode_type(current_translation().keycode_type),
+            scan_delay,
+            deltas_in_average,
+            key_press_distance,
+            Clock::real(),
+        )
+    }
+
+    /// Creates a delta watcher which reads the given source in its own thread.
+    /// Velocities and event times are measured with the clock,
+    /// give it the clock of the runtime when that is manual.
+    pub fn with_source<S: AnalogSource + 'static>(
+        source: S,
+        scan_delay: Duration,
+        deltas_in_average: u64,
+        key_press_distance: u8,
+        clock: Clock,
+    ) -> Self {
+        let s_ret = Self::unstarted(scan_delay, deltas_in_average, key_press_distance);
+
+        let s = s_ret.clone();
+        thread::spawn(move || {
+            delta_watcher(s, source, clock);
+        });
+
+        s_ret
+    }
+
+    /// Creates a delta watcher without a thread.
+    /// The source is read every time `poll` is called instead. (once per runtime loop)
+    /// Useful for deterministic runs with a [`ScriptedAnalog`](crate::keyboard::ScriptedAnalog).
+    pub fn polled<S: AnalogSource + 'static>(mut source: S, key_press_distance: u8) -> Self {
+        let mut s = Self::unstarted(Duration::ZERO, 10, key_press_distance);
+        if let Err(err) = source.init() {
+            println!("{err}");
+        }
+        let scanner = Scanner::new(&s);
+        s.polled = Some(Arc::new(Mutex::new(Polled {
+            source: Box::new(source),
+            scanner,
+            samples: Vec::new(),
+        })));
+        s
+    }
+
+    fn unstarted(scan_delay: Duration, deltas_in_average: u64, key_press_distance: u8) -> Self {
+        let (event_sender, events) = sync_channel(EVENT_QUEUE_LEN);
+        Self {
+            keys: current_translation()
+                .codes()
+                .map(|(scan_code, key)| KeyDelta {
+                    key,
                     scan_code,
                     delta: Arc::new(0.into()),
                     distance: Arc::new(0.into()),
                     last_distance: Arc::new(0.into()),
                     delta_average: Arc::new(0.into()),
+                    velocity: Arc::new(0.into()),
+                    acceleration: Arc::new(0.into()),
+                    peak_velocity: Arc::new(0.into()),
+                    keystrokes: Default::default(),
+                    total_travel: Arc::new(0.into()),
                     just_pressed: Arc::new(false.into()),
+                    pressed: Arc::new(false.into()),
                     key_press_distance: Arc::new(key_press_distance.into()),
+                    reset_distance: Arc::new(
+                        key_press_distance.saturating_sub(DEFAULT_HYSTERESIS).into(),
+                    ),
+                    rapid_trigger: Arc::new(0.into()),
                 })
-            },
+                .collect(),
+            unmapped: Default::default(),
             scan_delay_ns: Arc::new((scan_delay.as_nanos() as u64).into()),
             deltas_in_average: Arc::new(deltas_in_average.into()),
             exit: Arc::new(false.into()),
             mat_keys: get_matrix(),
             idle: Arc::new(false.into()),
-        };
-
-        let s = s_ret.clone();
-        thread::spawn(move || {
-            delta_watcher(s);
-        });
+            distance_thresholds: Default::default(),
+            hold_time_ns: Arc::new((DEFAULT_HOLD_TIME.as_nanos() as u64).into()),
+            velocity_filter: Default::default(),
+            keystroke_history: Arc::new(DEFAULT_KEYSTROKE_HISTORY.into()),
+            dropped_events: Default::default(),
+            events: Arc::new(Mutex::new(events)),
+            event_sender,
+            subscribers: Default::default(),
+            polled: None,
+        }
+    }
 
-        s_ret
+    /// Reads the source of a polled watcher once.
+    /// Does nothing on watchers that have their own thread.
+    /// This is automatically called by the runtime.
+    pub fn poll(&self, delta: Duration) {
+        if let Some(polled) = &self.polled {
+            let Polled {
+                source,
+                scanner,
+                samples,
+            } = &mut *polled.lock().unwrap();
+            samples.clear();
+            match source.read(samples) {
+                Ok(()) => scanner.scan(samples, delta),
+                Err(err) => println!("Error while reading values: {err}"),
+            }
+        }
     }
 
     /// Creates an uninitialized dummy delta watcher
     pub fn dummy() -> Self {
+        let (event_sender, events) = sync_channel(0);
         Self {
-            keys: SCAN_CODES.map(|_code| KeyDelta::default()),
+            keys: Vec::new(),
+            unmapped: Default::default(),
             scan_delay_ns: Default::default(),
             deltas_in_average: Default::default(),
             exit: Default::default(),
             mat_keys: get_matrix(),
             idle: Arc::new(true.into()),
+            distance_thresholds: Default::default(),
+            hold_time_ns: Default::default(),
+            velocity_filter: Default::default(),
+            keystroke_history: Default::default(),
+            dropped_events: Default::default(),
+            events: Arc::new(Mutex::new(events)),
+            event_sender,
+            subscribers: Default::default(),
+            polled: None,
+        }
+    }
+
+    /// Use the actuation for every key.
+    pub fn set_actuation(&self, actuation: Actuation) {
+        for key in self.keys.iter() {
+            key.set_actuation(actuation);
         }
     }
 
+    /// Use the actuation for the key at the matrix position.
+    pub fn set_key_actuation(&self, key: (u8, u8), actuation: Actuation) {
+        for key in self.keys.iter().filter(|dkey| dkey.key == key) {
+            key.set_actuation(actuation);
+        }
+    }
+
+    /// Takes the key events that happened since the last call in the order they happened.
+    /// The runtime takes these every loop and passes them to `Process::input`,
+    /// so this is only useful without a runtime.
+    /// At most `EVENT_QUEUE_LEN` events wait here, newer ones are dropped and counted
+    /// in `dropped_events`.
+    pub fn events(&self) -> Vec<KeyEvent> {
+        self.events.lock().unwrap().try_iter().collect()
+    }
+
+    /// Get every key event from now on in a queue of your own, for example for an effect.
+    /// These don't take the events from `events` or the runtime.
+    /// Like `events`, the queue holds `EVENT_QUEUE_LEN` events and drops the rest.
+    pub fn subscribe(&self) -> Receiver<KeyEvent> {
+        let (sender, receiver) = sync_channel(EVENT_QUEUE_LEN);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Last finished keystrokes of the key at the matrix position, oldest first.
+    /// Includes the strokes of every code that is mapped to the key.
+    pub fn keystrokes(&self, key: (u8, u8)) -> Vec<Keystroke> {
+        let mut keystrokes = self
+            .keys
+            .iter()
+            .filter(|dkey| dkey.key == key)
+            .flat_map(|dkey| dkey.keystrokes())
+            .collect::<Vec<_>>();
+        keystrokes.sort_by_key(|stroke| stroke.start);
+        keystrokes
+    }
+
+    /// Codes the source reported that are not in the translation.
+    pub fn unmapped_codes(&self) -> Vec<u16> {
+        self.unmapped.lock().unwrap().clone()
+    }
+
     /// Get all keys that are pressed down even slightly.
     pub fn get_pressed_keys(&self) -> Vec<&KeyDelta> {
         self.keys
@@ -116,17 +401,8 @@ impl DeltaWatcher {
     /// Get all keys that are pressed down with their associated matrix key
     pub fn get_pressed_keys_mat_keys(&self) -> Vec<(&KeyDelta, &Key)> {
         self.get_pressed_keys()
-            .iter()
-            .map(|key| {
-                (
-                    *key,
-                    self.mat_keys
-                        .as_flattened()
-                        .iter()
-                        .find(|mat_key| mat_key.key == key.key)
-                        .unwrap(),
-                )
-            })
+            .into_iter()
+            .filter_map(|key| Some((key, self.mat_keys.get(key.key)?)))
             .collect()
     }
 
@@ -139,65 +415,187 @@ impl DeltaWatcher {
     }
 }
 
+/// How long a key is pressed down before it sends a `Held` event by default
+pub const DEFAULT_HOLD_TIME: Duration = Duration::from_millis(500);
+
 impl Default for DeltaWatcher {
     fn default() -> Self {
         Self::new(Duration::from_millis(1), 50, 255 / 2)
     }
 }
 
-fn delta_watcher(s: DeltaWatcher) {
-    unsafe { wooting_analog_initialise() };
-    assert!(
-        is_initialised(),
-        "Wooting analog SDK is not initialised, no deltas will be provided"
-    );
-    unsafe { wooting_analog_set_keycode_mode(wooting_analog_wrapper::KeycodeType::ScanCode1) };
-
-    let mut keys: [(KeyDelta, AllocRingBuffer<i32>); SCAN_CODE_LEN] = s.keys.clone().map(|key| {
-        (
-            key,
-            AllocRingBuffer::new(s.deltas_in_average.load(Relaxed) as usize),
-        )
-    });
+/// Turns raw analog samples into key deltas and events.
+#[derive(Debug)]
+struct Scanner {
+    keys: Vec<ScanKey>,
+    deltas_in_average: Arc<AtomicU64>,
+    mapped: HashSet<u16>,
+    unmapped: Arc<Mutex<Vec<u16>>>,
+    distance_thresholds: Arc<Mutex<Vec<u8>>>,
+    hold_time_ns: Arc<AtomicU64>,
+    velocity_filter: Arc<Mutex<VelocityFilter>>,
+    keystroke_history: Arc<AtomicU64>,
+    dropped_events: Arc<AtomicU64>,
+    events: SyncSender<KeyEvent>,
+    subscribers: Arc<Mutex<Vec<SyncSender<KeyEvent>>>>,
+    /// Time since the scanner started
+    elapsed: Duration,
+}
 
-    let capacity: usize = SCAN_CODE_LEN;
-    let mut code_buffer: Vec<c_ushort> = vec![0; capacity];
-    let mut analog_buffer: Vec<c_float> = vec![0.0; capacity];
+#[derive(Debug)]
+struct ScanKey {
+    delta: KeyDelta,
+    deltas: AllocRingBuffer<i32>,
+    /// When the key was pressed down if it still is
+    pressed_at: Option<Duration>,
+    /// Whether the held event has been sent for this press
+    held: bool,
+    trigger: Trigger,
+    /// Distance of the last scan from 0 to 1
+    travel: f64,
+    velocity: Filtered,
+    acceleration: Filtered,
+    stroke: StrokeTracker,
+}
 
-    let mut last = Instant::now();
-    loop {
-        let now = Instant::now();
-        let delta = now.duration_since(last);
+/// Actuation state of a key.
+#[derive(Debug, Default)]
+struct Trigger {
+    /// Deepest distance while pressed and highest while released for rapid trigger
+    extreme: u8,
+    /// Whether rapid trigger can press the key before it reaches the actuation point
+    rapid_armed: bool,
+}
 
-        let result: c_int = unsafe {
-            wooting_analog_read_full_buffer(
-                code_buffer.as_mut_ptr(),
-                analog_buffer.as_mut_ptr(),
-                capacity as c_uint,
-            )
+impl Trigger {
+    /// Update the actuation state of the key with a new distance.
+    /// Returns Some(true) when the key gets pressed and Some(false) when it gets released.
+    fn actuate(&mut self, key: &KeyDelta, distance: u8) -> Option<bool> {
+        // The distances can also be set directly, so don't trust them
+        let actuation = key.actuation().clamped();
+        let reset = actuation.reset;
+        let pressed = key.is_pressed();
+
+        let change = if pressed {
+            let rapid_release = actuation
+                .rapid_trigger
+                .is_some_and(|sensitivity| distance.saturating_add(sensitivity) <= self.extreme);
+            if distance <= reset || rapid_release {
+                self.rapid_armed = actuation.rapid_trigger.is_some() && distance > reset;
+                Some(false)
+            } else {
+                None
+            }
+        } else {
+            if distance <= reset {
+                self.rapid_armed = false;
+            }
+            let rapid_press = self.rapid_armed
+                && actuation.rapid_trigger.is_some_and(|sensitivity| {
+                    distance >= self.extreme.saturating_add(sensitivity)
+                });
+            (distance >= actuation.press || rapid_press).then_some(true)
         };
 
-        if result < 0 {
-            println!("Error while reading values: {result}");
-            break;
+        match change {
+            Some(pressed) => {
+                key.pressed.store(pressed, Relaxed);
+                self.extreme = distance;
+            }
+            None if pressed => self.extreme = self.extreme.max(distance),
+            None => self.extreme = self.extreme.min(distance),
         }
+        change
+    }
+}
 
-        for (key, deltas_ring_buf) in keys.iter_mut() {
-            let key_press_distance = key.key_press_distance.load(Relaxed);
+impl Scanner {
+    fn new(watcher: &DeltaWatcher) -> Self {
+        Self {
+            keys: watcher
+                .keys
+                .iter()
+                .map(|key| ScanKey {
+                    delta: key.clone(),
+                    deltas: AllocRingBuffer::new(
+                        watcher.deltas_in_average.load(Relaxed).max(1) as usize
+                    ),
+                    pressed_at: None,
+                    held: false,
+                    trigger: Trigger::default(),
+                    travel: 0.0,
+                    velocity: Filtered::default(),
+                    acceleration: Filtered::default(),
+                    stroke: StrokeTracker::default(),
+                })
+                .collect(),
+            deltas_in_average: watcher.deltas_in_average.clone(),
+            mapped: watcher.keys.iter().map(|key| key.scan_code).collect(),
+            unmapped: watcher.unmapped.clone(),
+            distance_thresholds: watcher.distance_thresholds.clone(),
+            hold_time_ns: watcher.hold_time_ns.clone(),
+            velocity_filter: watcher.velocity_filter.clone(),
+            keystroke_history: watcher.keystroke_history.clone(),
+            dropped_events: watcher.dropped_events.clone(),
+            events: watcher.event_sender.clone(),
+            subscribers: watcher.subscribers.clone(),
+            elapsed: Duration::ZERO,
+        }
+    }
 
-            if deltas_ring_buf.len() != s.deltas_in_average.load(Relaxed) as usize {
-                *deltas_ring_buf = AllocRingBuffer::new(s.deltas_in_average.load(Relaxed) as usize);
+    /// Update keys based on samples of scan codes and their distances (from 0 to 1)
+    fn scan(&mut self, samples: &[(u16, f32)], delta: Duration) {
+        for (code, _) in samples.iter() {
+            if !self.mapped.contains(code) {
+                let mut unmapped = self.unmapped.lock().unwrap();
+                if !unmapped.contains(code) {
+                    unmapped.push(*code);
+                }
             }
+        }
 
-            for i in 0..result as usize {
-                let key_code = code_buffer[i];
-                let distance = analog_buffer[i];
+        self.elapsed += delta;
+        let thresholds = self.distance_thresholds.lock().unwrap().clone();
+        let hold_time = Duration::from_nanos(self.hold_time_ns.load(Relaxed));
+        let velocity_filter = *self.velocity_filter.lock().unwrap();
+        let keystroke_history = self.keystroke_history.load(Relaxed) as usize;
+        let dt = delta.as_secs_f64();
+        let mut events = Vec::new();
+        let mut sent = Vec::new();
+
+        let deltas_in_average = self.deltas_in_average.load(Relaxed).max(1) as usize;
+        for scan_key in self.keys.iter_mut() {
+            let key = &scan_key.delta;
+            let deltas_ring_buf = &mut scan_key.deltas;
+
+            if deltas_ring_buf.capacity() != deltas_in_average {
+                *deltas_ring_buf = AllocRingBuffer::new(deltas_in_average);
+            }
+
+            for (key_code, distance) in samples.iter().copied() {
                 if key.scan_code == key_code {
                     let distance = (distance * 255.0).round() as u8;
                     let last_distance = key.distance.load(Relaxed);
 
-                    if distance >= key_press_distance && last_distance < key_press_distance {
-                        key.just_pressed.store(true, Relaxed);
+                    for threshold in thresholds.iter().copied() {
+                        if (distance >= threshold) != (last_distance >= threshold) {
+                            events.push((KeyEventKind::DistanceCrossed(threshold), distance));
+                        }
+                    }
+                    match scan_key.trigger.actuate(key, distance) {
+                        Some(true) => {
+                            key.just_pressed.store(true, Relaxed);
+                            events.push((KeyEventKind::Pressed, distance));
+                            scan_key.pressed_at = Some(self.elapsed);
+                            scan_key.held = false;
+                        }
+                        Some(false) => {
+                            events.push((KeyEventKind::Released, distance));
+                            if let Some(pressed_at) = scan_key.pressed_at.take() {
+                                scan_key.stroke.actuated(self.elapsed - pressed_at);
+                            }
+                        }
+                        None => (),
                     }
 
                     key.last_distance.store(last_distance, Relaxed);
@@ -207,7 +605,8 @@ fn delta_watcher(s: DeltaWatcher) {
                     let dt = delta.as_secs_f64() * 10.0;
 
                     let mut v = dx / dt;
-                    if v.is_nan() {
+                    // No time has passed on a manual clock
+                    if !v.is_finite() {
                         v = 0.0;
                     }
 
@@ -215,17 +614,149 @@ fn delta_watcher(s: DeltaWatcher) {
                     key.delta.store(v, Relaxed);
                 }
             }
-            if code_buffer[0..result as usize].contains(&key.scan_code) {
+            if samples.iter().any(|(code, _)| *code == key.scan_code) {
                 let v = key.delta.load(Relaxed);
                 deltas_ring_buf.enqueue(v);
             } else {
                 deltas_ring_buf.enqueue(0);
             }
-            let delta_average =
-                deltas_ring_buf.iter().sum::<i32>() / s.deltas_in_average.load(Relaxed) as i32;
+            let delta_average = deltas_ring_buf.iter().sum::<i32>() / deltas_ring_buf.len() as i32;
             key.delta_average.store(delta_average, Relaxed);
+
+            // Keys that are not in the samples are at rest
+            let travel = samples
+                .iter()
+                .find(|(code, _)| *code == key.scan_code)
+                .map(|(_, distance)| *distance as f64)
+                .unwrap_or(0.0);
+            // No time has passed on a manual clock, keep the last velocity then
+            let velocity = if dt > 0.0 {
+                let last_velocity = key.velocity();
+                let velocity =
+                    scan_key
+                        .velocity
+                        .update(velocity_filter, (travel - scan_key.travel) / dt, dt);
+                let acceleration = scan_key.acceleration.update(
+                    velocity_filter,
+                    (velocity - last_velocity) / dt,
+                    dt,
+                );
+                key.velocity.store(velocity.to_bits(), Relaxed);
+                key.acceleration.store(acceleration.to_bits(), Relaxed);
+                velocity
+            } else {
+                key.velocity()
+            };
+
+            if travel > 0.0 {
+                // A new keystroke starts when the key leaves rest
+                let peak = if scan_key.travel == 0.0 {
+                    0.0
+                } else {
+                    key.peak_velocity()
+                };
+                key.peak_velocity
+                    .store(peak.max(velocity).to_bits(), Relaxed);
+            }
+            let total_travel = key.total_travel() + (travel - scan_key.travel).abs();
+            key.total_travel.store(total_travel.to_bits(), Relaxed);
+            scan_key.travel = travel;
+
+            if let Some(stroke) = scan_key.stroke.update(
+                key.key,
+                key.scan_code,
+                travel,
+                velocity,
+                delta,
+                self.elapsed,
+            ) {
+                let mut keystrokes = key.keystrokes.lock().unwrap();
+                keystrokes.push_back(stroke);
+                while keystrokes.len() > keystroke_history {
+                    keystrokes.pop_front();
+                }
+            }
+
+            if let Some(pressed_at) = scan_key.pressed_at
+                && !scan_key.held
+                && self.elapsed - pressed_at >= hold_time
+            {
+                scan_key.held = true;
+                events.push((
+                    KeyEventKind::Held(self.elapsed - pressed_at),
+                    key.distance.load(Relaxed),
+                ));
+            }
+
+            for (kind, distance) in events.drain(..) {
+                let event = KeyEvent {
+                    kind,
+                    key: key.key,
+                    scan_code: key.scan_code,
+                    distance,
+                    time: self.elapsed,
+                };
+                if let Err(TrySendError::Full(_)) = self.events.try_send(event) {
+                    self.dropped_events.fetch_add(1, Relaxed);
+                }
+                sent.push(event);
+            }
         }
 
+        if !sent.is_empty() {
+            // Forget subscribers that have been dropped
+            self.subscribers.lock().unwrap().retain(|subscriber| {
+                for event in sent.iter() {
+                    match subscriber.try_send(*event) {
+                        Ok(()) => (),
+                        Err(TrySendError::Full(_)) => {
+                            self.dropped_events.fetch_add(1, Relaxed);
+                        }
+                        Err(TrySendError::Disconnected(_)) => return false,
+                    }
+                }
+                true
+            });
+        }
+    }
+}
+
+/// Source and scanner of a watcher that is polled by the runtime.
+struct Polled {
+    source: Box<dyn AnalogSource>,
+    scanner: Scanner,
+    samples: Vec<(u16, f32)>,
+}
+
+impl Debug for Polled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Polled")
+            .field("scanner", &self.scanner)
+            .finish_non_exhaustive()
+    }
+}
+
+fn delta_watcher<S: AnalogSource>(s: DeltaWatcher, mut source: S, clock: Clock) {
+    if let Err(err) = source.init() {
+        println!("{err}");
+        return;
+    }
+
+    let mut scanner = Scanner::new(&s);
+    let mut samples: Vec<(u16, f32)> = Vec::with_capacity(SCAN_CODE_LEN);
+
+    let mut last = clock.now();
+    loop {
+        let now = clock.now();
+        let delta = now.duration_since(last);
+
+        samples.clear();
+        if let Err(err) = source.read(&mut samples) {
+            println!("Error while reading values: {err}");
+            break;
+        }
+        scanner.scan(&samples, delta);
+
         sleep(Duration::from_nanos(s.scan_delay_ns.load(Relaxed)));
         if s.idle.load(Relaxed) {
             sleep(Duration::from_millis(200));
@@ -238,9 +769,7 @@ fn delta_watcher(s: DeltaWatcher) {
         }
     }
 
-    if is_initialised() {
-        unsafe { wooting_analog_uninitialise() };
-    }
+    source.close();
 
     println!("Exited delta watcher");
 }
@@ -0,0 +1,172 @@
+use std::time::Duration;
+
+/// How many keystrokes are kept per key by default
+pub const DEFAULT_KEYSTROKE_HISTORY: u64 = 32;
+
+/// How close to the deepest point the key has to be to count as bottomed out (from 0 to 1)
+const BOTTOM_MARGIN: f64 = 0.05;
+
+/// One stroke of a key from leaving rest to coming back to it.
+/// Times are since the delta watcher started.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Keystroke {
+    /// Position of the key in the rgb matrix
+    pub key: (u8, u8),
+    pub scan_code: u16,
+    /// When the key left rest
+    pub start: Duration,
+    /// When the key came back to rest
+    pub end: Duration,
+    /// Deepest distance the key reached (from 0 to 1)
+    pub max_depth: f64,
+    /// How long the key stayed near its deepest point
+    pub time_at_bottom: Duration,
+    /// How long the key was actuated, from press to release
+    pub dwell: Duration,
+    /// Highest press velocity in full travels per second
+    pub peak_velocity: f64,
+}
+
+impl Keystroke {
+    /// How long the whole stroke took.
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Whether the key went down far enough to be pressed.
+    pub fn actuated(&self) -> bool {
+        !self.dwell.is_zero()
+    }
+}
+
+/// Builds keystrokes of a single key out of scans.
+#[derive(Debug, Default)]
+pub(crate) struct StrokeTracker {
+    current: Option<Keystroke>,
+}
+
+impl StrokeTracker {
+    /// Update the stroke with the distance of the key (from 0 to 1) dt after the last scan.
+    /// Returns the stroke once the key is back at rest.
+    pub(crate) fn update(
+        &mut self,
+        key: (u8, u8),
+        scan_code: u16,
+        travel: f64,
+        velocity: f64,
+        dt: Duration,
+        now: Duration,
+    ) -> Option<Keystroke> {
+        if travel <= 0.0 {
+            return self
+                .current
+                .take()
+                .map(|stroke| Keystroke { end: now, ..stroke });
+        }
+
+        // The time before the stroke started isn't spent at the bottom
+        let (stroke, dt) = match &mut self.current {
+            Some(stroke) => (stroke, dt),
+            None => (
+                self.current.insert(Keystroke {
+                    key,
+                    scan_code,
+                    start: now,
+                    end: now,
+                    ..Default::default()
+                }),
+                Duration::ZERO,
+            ),
+        };
+        if travel > stroke.max_depth {
+            // Went deeper than the bottom so far, start timing the new one
+            if travel > stroke.max_depth + BOTTOM_MARGIN {
+                stroke.time_at_bottom = Duration::ZERO;
+            }
+            stroke.max_depth = travel;
+        }
+        if travel >= stroke.max_depth - BOTTOM_MARGIN {
+            stroke.time_at_bottom += dt;
+        }
+        stroke.peak_velocity = stroke.peak_velocity.max(velocity);
+        None
+    }
+
+    /// The key was actuated for the duration during this stroke.
+    pub(crate) fn actuated(&mut self, duration: Duration) {
+        if let Some(stroke) = &mut self.current {
+            stroke.dwell += duration;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    /// Feeds the travels to the tracker one millisecond apart.
+    fn strokes(tracker: &mut StrokeTracker, travels: &[f64], start: u32) -> Vec<Keystroke> {
+        travels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, travel)| {
+                tracker.update(
+                    (1, 0),
+                    1,
+                    *travel,
+                    *travel * 10.0,
+                    MS,
+                    MS * (start + i as u32),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn press_bottom_and_release() {
+        let mut tracker = StrokeTracker::default();
+        let strokes = strokes(&mut tracker, &[0.0, 0.5, 1.0, 1.0, 0.98, 0.5, 0.0], 0);
+
+        assert_eq!(strokes.len(), 1);
+        let stroke = strokes[0];
+        assert_eq!(stroke.key, (1, 0));
+        assert_eq!(stroke.start, MS);
+        assert_eq!(stroke.end, MS * 6);
+        assert_eq!(stroke.duration(), MS * 5);
+        assert_eq!(stroke.max_depth, 1.0);
+        assert_eq!(stroke.time_at_bottom, MS * 3);
+        assert_eq!(stroke.peak_velocity, 10.0);
+        assert!(!stroke.actuated());
+    }
+
+    #[test]
+    fn first_scan_is_not_at_the_bottom() {
+        let mut tracker = StrokeTracker::default();
+        let strokes = strokes(&mut tracker, &[1.0, 0.0], 0);
+        assert_eq!(strokes[0].time_at_bottom, Duration::ZERO);
+    }
+
+    #[test]
+    fn closes_without_time_passing() {
+        let mut tracker = StrokeTracker::default();
+        assert!(tracker.update((1, 0), 1, 0.5, 0.0, MS, MS).is_none());
+        let stroke = tracker.update((1, 0), 1, 0.0, 0.0, Duration::ZERO, MS);
+        assert!(stroke.is_some());
+    }
+
+    #[test]
+    fn dwell_adds_up() {
+        let mut tracker = StrokeTracker::default();
+        // Not in a stroke yet
+        tracker.actuated(MS);
+        strokes(&mut tracker, &[0.5], 0);
+        tracker.actuated(MS * 2);
+        tracker.actuated(MS * 3);
+        let strokes = strokes(&mut tracker, &[0.0], 1);
+
+        assert_eq!(strokes[0].dwell, MS * 5);
+        assert!(strokes[0].actuated());
+    }
+}
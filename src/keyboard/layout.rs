@@ -0,0 +1,322 @@
+use std::{
+    path::Path,
+    sync::{LazyLock, RwLock},
+};
+
+use nalgebra::Vector2;
+
+use crate::{
+    Bounds,
+    keyboard::{
+        KeyboardMatrix,
+        matrix::{RAW_MATRIX, compute_bounds},
+    },
+};
+
+/// Layout get_matrix uses. None means the 60HE.
+static CURRENT: RwLock<Option<Layout>> = RwLock::new(None);
+
+/// Parsed once since the default layout is needed often
+static DEFAULT: LazyLock<Layout> = LazyLock::new(Layout::wooting_60he);
+
+/// A single physical key of a layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutKey {
+    /// Position of the key in the rgb matrix (row, column)
+    pub matrix: (u8, u8),
+    /// Centre of the key in key units
+    pub position: Vector2<f64>,
+    /// Width and height of the key in key units
+    pub size: Vector2<f64>,
+    /// Rotation around the centre in degrees
+    pub rotation: f64,
+    pub label: String,
+    /// ScanCode1 codes the key can send
+    pub scan_codes: Vec<u16>,
+}
+
+/// Physical layout of a keyboard.
+///
+/// Can be loaded from a text file with one key per line:
+/// ```text
+/// # row column x y width height label scan_codes
+/// name My keyboard
+/// 1 0 0 1 1 1 Esc 1
+/// 1 13 13.5 1 2 1 Backspace 14
+/// ```
+/// `#` starts a comment. Scan codes are separated by commas and can be left out.
+/// Rotated keys can add `rotation=<degrees>` to the end of the line.
+/// An optional `frame x y width height` line sets the area that is normalized to -0.5..0.5.
+/// By default the area covers the centres of all keys.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layout {
+    pub name: String,
+    pub keys: Vec<LayoutKey>,
+    /// Area that is normalized to -0.5..0.5
+    pub frame: Option<Bounds>,
+}
+
+impl Layout {
+    /// Wooting 60HE with an ISO Nordic layout.
+    /// This is what `get_matrix` uses by default.
+    pub fn wooting_60he() -> Self {
+        Self::parse(include_str!("layouts/wooting_60he.txt")).expect("Dev error")
+    }
+
+    /// Wooting Two HE with an ISO Nordic layout.
+    pub fn wooting_two_he() -> Self {
+        Self::parse(include_str!("layouts/wooting_two_he.txt")).expect("Dev error")
+    }
+
+    /// Wooting 80HE with an ISO Nordic layout.
+    pub fn wooting_80he() -> Self {
+        Self::parse(include_str!("layouts/wooting_80he.txt")).expect("Dev error")
+    }
+
+    /// Wooting UwU
+    pub fn wooting_uwu() -> Self {
+        Self::parse(include_str!("layouts/wooting_uwu.txt")).expect("Dev error")
+    }
+
+    /// Load a layout from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse(&text)
+    }
+
+    /// Parse a layout from text.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut layout = Layout::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: &str| format!("Line {}: {msg}", i + 1);
+
+            if let Some(name) = line.strip_prefix("name ") {
+                layout.name = name.trim().to_string();
+                continue;
+            }
+            if let Some(frame) = line.strip_prefix("frame ") {
+                let values = frame
+                    .split_whitespace()
+                    .map(|v| v.parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| error(&err.to_string()))?;
+                let [x, y, w, h] = values[..] else {
+                    return Err(error("Expected frame x y width height"));
+                };
+                layout.frame = Some(Bounds {
+                    position: Vector2::new(x, y),
+                    size: Vector2::new(w, h),
+                });
+                continue;
+            }
+
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            if parts.len() < 7 || parts.len() > 9 {
+                return Err(error(
+                    "Expected row column x y width height label scan_codes",
+                ));
+            }
+            let int = |s: &str| s.parse::<u8>().map_err(|err| error(&err.to_string()));
+            let float = |s: &str| s.parse::<f64>().map_err(|err| error(&err.to_string()));
+            let mut scan_codes = Vec::new();
+            let mut rotation = 0.0;
+            for part in parts[7..].iter() {
+                if let Some(degrees) = part.strip_prefix("rotation=") {
+                    rotation = float(degrees)?;
+                } else {
+                    scan_codes = part
+                        .split(',')
+                        .map(|code| code.parse::<u16>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|err| error(&err.to_string()))?;
+                }
+            }
+
+            let key = LayoutKey {
+                matrix: (int(parts[0])?, int(parts[1])?),
+                position: Vector2::new(float(parts[2])?, float(parts[3])?),
+                size: Vector2::new(float(parts[4])?, float(parts[5])?),
+                rotation,
+                label: parts[6].to_string(),
+                scan_codes,
+            };
+            if key.matrix.0 as usize >= RAW_MATRIX.len()
+                || key.matrix.1 as usize >= RAW_MATRIX[0].len()
+            {
+                return Err(error("Matrix position is outside of the keyboard"));
+            }
+            if layout.key(key.matrix).is_some() {
+                return Err(error("Matrix position is used twice"));
+            }
+            layout.keys.push(key);
+        }
+
+        Ok(layout)
+    }
+
+    /// Save the layout to a file in the format `load` reads.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        std::fs::write(path, self.to_text()).map_err(|err| err.to_string())
+    }
+
+    /// The layout in the format `parse` reads.
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# row column x y width height label scan_codes\n");
+        if !self.name.is_empty() {
+            text += &format!("name {}\n", self.name);
+        }
+        if let Some(frame) = self.frame {
+            text += &format!(
+                "frame {} {} {} {}\n",
+                frame.position.x, frame.position.y, frame.size.x, frame.size.y
+            );
+        }
+        for key in self.keys.iter() {
+            text += &format!(
+                "{} {} {} {} {} {} {}",
+                key.matrix.0,
+                key.matrix.1,
+                key.position.x,
+                key.position.y,
+                key.size.x,
+                key.size.y,
+                key.label
+            );
+            if !key.scan_codes.is_empty() {
+                let codes = key
+                    .scan_codes
+                    .iter()
+                    .map(|code| code.to_string())
+                    .collect::<Vec<_>>();
+                text += &format!(" {}", codes.join(","));
+            }
+            if key.rotation != 0.0 {
+                text += &format!(" rotation={}", key.rotation);
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Key at the matrix position if the layout has one.
+    pub fn key(&self, matrix: (u8, u8)) -> Option<&LayoutKey> {
+        self.keys.iter().find(|key| key.matrix == matrix)
+    }
+
+    /// Key with the label if the layout has one.
+    pub fn key_by_label(&self, label: &str) -> Option<&LayoutKey> {
+        self.keys.iter().find(|key| key.label == label)
+    }
+
+    /// Whether this is an ISO layout.
+    /// The key with ScanCode1 43 is on the home row next to enter on ISO boards
+    /// and above enter on ANSI ones.
+    pub fn is_iso(&self) -> bool {
+        let row = |code: u16| {
+            self.keys
+                .iter()
+                .find(|key| key.scan_codes.contains(&code))
+                .map(|key| key.matrix.0)
+        };
+        // 30 is A
+        row(43).is_some_and(|row_43| row(30) == Some(row_43))
+    }
+
+    /// Matrix positions of the keys with the labels. Labels the layout doesn't have are skipped.
+    pub fn positions_of(&self, labels: &[&str]) -> Vec<(u8, u8)> {
+        labels
+            .iter()
+            .filter_map(|label| self.key_by_label(label).map(|key| key.matrix))
+            .collect()
+    }
+
+    /// Area that is normalized to -0.5..0.5.
+    pub fn frame(&self) -> Bounds {
+        match self.frame {
+            Some(frame) => frame,
+            None => bounds_of(self.keys.iter().map(|key| key.position)),
+        }
+    }
+
+    /// Keyboard matrix with the geometry of this layout.
+    /// Matrix positions the layout doesn't have are placed on their column and row.
+    pub fn matrix(&self) -> KeyboardMatrix {
+        let mut matrix = if self.keys.is_empty() {
+            KeyboardMatrix::new(RAW_MATRIX.len(), RAW_MATRIX[0].len())
+        } else {
+            let rows = self.keys.iter().map(|key| key.matrix.0).max().unwrap_or(0);
+            let columns = self.keys.iter().map(|key| key.matrix.1).max().unwrap_or(0);
+            let labels = self
+                .keys
+                .iter()
+                .map(|key| (key.matrix.0, key.matrix.1, key.label.clone()))
+                .collect::<Vec<_>>();
+            KeyboardMatrix::with_layout(rows as usize + 1, columns as usize + 1, &labels)
+        };
+        let frame = self.frame();
+        // Avoid dividing by zero with layouts of a single row or column
+        let size = frame.size.map(|v| if v > 0.0 { v } else { 1.0 });
+        // and keep them centred
+        let origin = frame.position - (size - frame.size) * 0.5;
+        let aspect = size.x / size.y;
+
+        for key in matrix.as_flattened_mut() {
+            if let Some(layout_key) = self.key(key.key) {
+                key.physical_position = layout_key.position;
+                key.size = layout_key.size;
+                key.rotation = layout_key.rotation;
+            }
+            key.pos_norm = (key.physical_position - origin).component_div(&size);
+            // normalize
+            key.pos_norm -= Vector2::new(0.5, 0.5);
+            // aspect ratio corrected
+            key.pos_norm_aspect.x = key.pos_norm.x * aspect;
+            key.pos_norm_aspect.y = key.pos_norm.y;
+            key.size_norm = key.size.component_div(&size);
+            key.size_norm_aspect = Vector2::new(key.size_norm.x * aspect, key.size_norm.y);
+        }
+
+        matrix
+    }
+
+    /// Bounds of the normalized positions of the keys on this layout.
+    pub fn bounds(&self) -> Bounds {
+        compute_bounds(&self.matrix())
+    }
+}
+
+/// Use the layout for `get_matrix` and everything built on it.
+/// Set this before creating the runtime.
+pub fn set_layout(layout: Layout) {
+    *CURRENT.write().unwrap() = Some(layout);
+}
+
+/// Layout `get_matrix` uses.
+pub fn current_layout() -> Layout {
+    CURRENT
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| DEFAULT.clone())
+}
+
+pub(crate) fn bounds_of(mut points: impl Iterator<Item = Vector2<f64>>) -> Bounds {
+    let Some(first) = points.next() else {
+        return Bounds::default();
+    };
+    let mut min = first;
+    let mut max = first;
+    for point in points {
+        min = min.inf(&point);
+        max = max.sup(&point);
+    }
+    Bounds {
+        position: min,
+        size: max - min,
+    }
+}
@@ -1,9 +1,235 @@
+use std::{
+    ops::{Index, IndexMut},
+    sync::Arc,
+};
+
 use nalgebra::Vector2;
 
-use crate::{Bounds, key::Key};
+use crate::{
+    Bounds,
+    key::Key,
+    keyboard::{
+        KeyGroup, Selection,
+        layout::{bounds_of, current_layout},
+    },
+};
+
+/// Keys of a keyboard in a grid of rows and columns, sized at runtime.
+/// Positions that have no key on the layout are still there, but they are left out of
+/// `keys` and the frames sent to the keyboard.
+/// Index with a row to get its keys, `matrix[1][0]`, or with a matrix position, `matrix[(1, 0)]`.
+/// Indexing panics outside of the matrix, `get` and `get_mut` return None instead.
+#[derive(Debug, Clone)]
+pub struct KeyboardMatrix {
+    rows: usize,
+    columns: usize,
+    keys: Vec<Key>,
+    /// Shared between clones since they don't change
+    slots: Arc<Vec<Slot>>,
+}
+
+/// What the layout says about a position of the matrix.
+#[derive(Debug, Clone, Default)]
+struct Slot {
+    present: bool,
+    label: Option<String>,
+}
+
+impl KeyboardMatrix {
+    /// Matrix where every position has a key placed on its column and row.
+    pub fn new(rows: usize, columns: usize) -> Self {
+        let mut keys = Vec::with_capacity(rows * columns);
+        for row in 0..rows {
+            for column in 0..columns {
+                keys.push(Key {
+                    key: (row as u8, column as u8),
+                    physical_position: Vector2::new(column as f64, row as f64),
+                    ..Default::default()
+                });
+            }
+        }
+        Self {
+            rows,
+            columns,
+            keys,
+            slots: Arc::new(vec![
+                Slot {
+                    present: true,
+                    label: None
+                };
+                rows * columns
+            ]),
+        }
+    }
+
+    /// Positions of the layout, the rest are left empty.
+    pub(crate) fn with_layout(rows: usize, columns: usize, keys: &[(u8, u8, String)]) -> Self {
+        let mut matrix = Self::new(rows, columns);
+        let mut slots = vec![Slot::default(); rows * columns];
+        for (row, column, label) in keys.iter() {
+            if let Some(i) = matrix.index((*row, *column)) {
+                slots[i] = Slot {
+                    present: true,
+                    label: Some(label.clone()),
+                };
+            }
+        }
+        matrix.slots = Arc::new(slots);
+        matrix
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    fn index(&self, (row, column): (u8, u8)) -> Option<usize> {
+        let (row, column) = (row as usize, column as usize);
+        (row < self.rows && column < self.columns).then_some(row * self.columns + column)
+    }
 
-pub type KeyboardMatrix = [[Key; 21]; 6];
+    /// Whether the layout has a key at the position.
+    pub fn contains(&self, position: (u8, u8)) -> bool {
+        self.index(position).is_some_and(|i| self.slots[i].present)
+    }
+
+    /// Key at the matrix position, even if the layout has no key there.
+    pub fn get(&self, position: (u8, u8)) -> Option<&Key> {
+        self.index(position).map(|i| &self.keys[i])
+    }
+
+    pub fn get_mut(&mut self, position: (u8, u8)) -> Option<&mut Key> {
+        self.index(position).map(|i| &mut self.keys[i])
+    }
+
+    /// Label of the key at the position from the layout.
+    pub fn label(&self, position: (u8, u8)) -> Option<&str> {
+        self.index(position)
+            .and_then(|i| self.slots[i].label.as_deref())
+    }
+
+    /// Key with the label on the layout.
+    pub fn by_label(&self, label: &str) -> Option<&Key> {
+        self.position_of(label)
+            .and_then(|position| self.get(position))
+    }
+
+    pub fn by_label_mut(&mut self, label: &str) -> Option<&mut Key> {
+        self.position_of(label)
+            .and_then(|position| self.get_mut(position))
+    }
+
+    /// Matrix position of the key with the label.
+    pub fn position_of(&self, label: &str) -> Option<(u8, u8)> {
+        self.slots
+            .iter()
+            .position(|slot| slot.label.as_deref() == Some(label))
+            .map(|i| self.keys[i].key)
+    }
+
+    /// Select the keys of the group to change them at once.
+    /// `layer.select(KeyGroup::Wasd).set_color(color)`
+    pub fn select(&mut self, group: KeyGroup) -> Selection<'_> {
+        self.select_labels(group.labels())
+    }
+
+    /// Select the keys with the labels. Labels the layout doesn't have are skipped.
+    pub fn select_labels(&mut self, labels: &[&str]) -> Selection<'_> {
+        let positions = labels
+            .iter()
+            .filter_map(|label| self.position_of(label))
+            .collect();
+        Selection::new(self, positions)
+    }
 
+    /// Select the keys on the layout the predicate returns true for.
+    pub fn select_where(&mut self, predicate: impl Fn(&Key) -> bool) -> Selection<'_> {
+        let positions = self
+            .keys()
+            .filter(|key| predicate(key))
+            .map(|key| key.key)
+            .collect();
+        Selection::new(self, positions)
+    }
+
+    /// Keys that are on the layout.
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.keys
+            .iter()
+            .zip(self.slots.iter())
+            .filter(|(_, slot)| slot.present)
+            .map(|(key, _)| key)
+    }
+
+    pub fn keys_mut(&mut self) -> impl Iterator<Item = &mut Key> {
+        self.keys
+            .iter_mut()
+            .zip(self.slots.iter())
+            .filter(|(_, slot)| slot.present)
+            .map(|(key, _)| key)
+    }
+
+    /// Every position of the matrix row by row, including empty ones.
+    pub fn as_flattened(&self) -> &[Key] {
+        &self.keys
+    }
+
+    pub fn as_flattened_mut(&mut self) -> &mut [Key] {
+        &mut self.keys
+    }
+
+    /// Rows of the matrix.
+    pub fn iter_rows(&self) -> impl Iterator<Item = &[Key]> {
+        self.keys.chunks(self.columns.max(1))
+    }
+
+    pub fn iter_rows_mut(&mut self) -> impl Iterator<Item = &mut [Key]> {
+        self.keys.chunks_mut(self.columns.max(1))
+    }
+}
+
+/// # Panics
+/// If the row is outside of the matrix.
+impl Index<usize> for KeyboardMatrix {
+    type Output = [Key];
+
+    fn index(&self, row: usize) -> &[Key] {
+        &self.keys[row * self.columns..(row + 1) * self.columns]
+    }
+}
+
+/// # Panics
+/// If the row is outside of the matrix.
+impl IndexMut<usize> for KeyboardMatrix {
+    fn index_mut(&mut self, row: usize) -> &mut [Key] {
+        &mut self.keys[row * self.columns..(row + 1) * self.columns]
+    }
+}
+
+/// # Panics
+/// If the position is outside of the matrix. Use `get` if it might be.
+impl Index<(u8, u8)> for KeyboardMatrix {
+    type Output = Key;
+
+    fn index(&self, position: (u8, u8)) -> &Key {
+        self.get(position)
+            .expect("Position is outside of the matrix")
+    }
+}
+
+/// # Panics
+/// If the position is outside of the matrix. Use `get_mut` if it might be.
+impl IndexMut<(u8, u8)> for KeyboardMatrix {
+    fn index_mut(&mut self, position: (u8, u8)) -> &mut Key {
+        self.get_mut(position)
+            .expect("Position is outside of the matrix")
+    }
+}
+
+/// Keys of the Wooting 60HE. See `Layout::wooting_60he` for their geometry.
 pub const W60HE_KEYS: [(u8, u8); 62] = [
     ESC,
     ONE,
@@ -127,7 +353,8 @@ pub const RAW_MATRIX_60HE: [[(u8, u8); 14]; 5] = [
     ],
 ];
 
-/// Raw full matrix of any wooting
+/// Raw full matrix of any wooting.
+/// Layouts can use any size, this is what Wooting keyboards have.
 pub const RAW_MATRIX: [[(u8, u8); 21]; 6] = [
     [
         (0, 0),
@@ -269,60 +496,20 @@ pub const RAW_MATRIX: [[(u8, u8); 21]; 6] = [
     ],
 ];
 
+/// Bounds of the normalized positions of the keys in the matrix.
+/// Only keys on the layout are counted.
 pub fn compute_bounds(matrix: &KeyboardMatrix) -> Bounds {
-    let points = matrix.map(|row| row.map(|key| key.pos_norm));
-    let points = points.as_flattened();
-    let mut min_x = points[0].x;
-    let mut max_x = points[0].x;
-    let mut min_y = points[0].y;
-    let mut max_y = points[0].y;
-
-    for point in points.iter().skip(1) {
-        min_x = min_x.min(point.x);
-        max_x = max_x.max(point.x);
-        min_y = min_y.min(point.y);
-        max_y = max_y.max(point.y);
-    }
-
-    Bounds {
-        position: Vector2::new(min_x, min_y),
-        size: Vector2::new(max_x - min_x, max_y - min_y),
+    if matrix.keys().next().is_some() {
+        bounds_of(matrix.keys().map(|key| key.pos_norm))
+    } else {
+        bounds_of(matrix.as_flattened().iter().map(|key| key.pos_norm))
     }
 }
 
-/// Get the full keyboard matrix where every position is included with key data
+/// Get the full keyboard matrix where every position is included with key data.
+/// Positions come from the current layout, see `set_layout`.
 pub fn get_matrix() -> KeyboardMatrix {
-    let mut matrix = [[Key::default(); 21]; 6];
-    let min: Vector2<f64> = Vector2::new(0.0, 0.0);
-    let max: Vector2<f64> = Vector2::new(14.0, 4.0);
-    let size = max - min;
-    let aspect = size.x / size.y;
-
-    for (x, row) in RAW_MATRIX.iter().enumerate() {
-        for (y, key_pos) in row.iter().enumerate() {
-            let key = &mut matrix[x][y];
-            key.key = *key_pos;
-            key.physical_position = match key.key {
-                BACKSPACE => Vector2::new(13.5, 1.0),
-                ENTER => Vector2::new(13.5, 2.5),
-                RIGHT_SHIFT => Vector2::new(12.5, 4.0),
-                _ => Vector2::new(key_pos.1 as f64, key_pos.0 as f64),
-            };
-            key.pos_norm = Vector2::new(
-                (key.physical_position.x - min.x) / size.x,
-                (key.physical_position.y - min.y) / size.y,
-            );
-            // normalize
-            key.pos_norm -= Vector2::new(0.5, 0.5);
-            // Apply manual offset for centre correction
-            key.pos_norm += Vector2::new(0.05, 0.0);
-            // aspect ratio corrected
-            key.pos_norm_aspect.x = key.pos_norm.x * aspect;
-            key.pos_norm_aspect.y = key.pos_norm.y;
-        }
-    }
-
-    matrix
+    current_layout().matrix()
 }
 
 pub const ESC: (u8, u8) = (1, 0);
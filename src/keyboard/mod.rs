@@ -1,7 +1,23 @@
+mod analog;
+mod event;
+mod filter;
+mod group;
 mod key_delta;
+mod keystroke;
+mod layout;
 pub mod matrix;
+mod scripted;
+mod session;
 mod translate;
 
+pub use analog::{AnalogSource, ReplaySource, WootingAnalog};
+pub use event::*;
+pub use filter::{TRAVEL_MM, VelocityFilter};
+pub use group::*;
 pub use key_delta::*;
+pub use keystroke::{DEFAULT_KEYSTROKE_HISTORY, Keystroke};
+pub use layout::*;
 pub use matrix::{get_matrix, KeyboardMatrix};
+pub use scripted::*;
+pub use session::*;
 pub use translate::*;
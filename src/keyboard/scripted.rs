@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+
+use crate::keyboard::{AnalogSource, analog::release_missing};
+
+/// Scripted analog input for running processes without an analog keyboard.
+/// Every read consumes one frame of the script.
+/// Use it with [`DeltaWatcher::polled`](crate::keyboard::DeltaWatcher::polled) to advance one
+/// frame per runtime loop.
+/// Like the analog SDK, keys released since the previous frame are reported once with a distance
+/// of 0.
+#[derive(Debug, Default, Clone)]
+pub struct ScriptedAnalog {
+    frames: VecDeque<Vec<(u16, f32)>>,
+    previous: Vec<u16>,
+}
+
+impl ScriptedAnalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a frame where the given scan codes are pressed down by the given distance.
+    /// (from 0 to 1, 1 being completely pressed down)
+    /// Keys that are not included are released.
+    pub fn frame(mut self, samples: &[(u16, f32)]) -> Self {
+        self.frames.push_back(samples.to_vec());
+        self
+    }
+
+    /// Add the same frame `count` times.
+    pub fn hold(mut self, samples: &[(u16, f32)], count: usize) -> Self {
+        for _ in 0..count {
+            self.frames.push_back(samples.to_vec());
+        }
+        self
+    }
+
+    /// Add `count` frames where nothing is pressed.
+    pub fn idle(self, count: usize) -> Self {
+        self.hold(&[], count)
+    }
+
+    /// Whether every frame of the script has been consumed.
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Next frame of samples.
+    /// Once the script is finished every key stays released.
+    pub fn next_samples(&mut self) -> Vec<(u16, f32)> {
+        let mut samples = self.frames.pop_front().unwrap_or_default();
+        release_missing(&mut self.previous, &mut samples);
+        samples
+    }
+}
+
+impl AnalogSource for ScriptedAnalog {
+    fn read(&mut self, samples: &mut Vec<(u16, f32)>) -> Result<(), String> {
+        samples.extend(self.next_samples());
+        Ok(())
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::Clock,
+    keyboard::{AnalogSource, analog::release_missing},
+};
+
+/// Magic bytes at the start of a session file.
+const MAGIC: &[u8; 4] = b"RGBS";
+const VERSION: u8 = 1;
+
+/// Slowest replay speed
+pub const MIN_SPEED: f64 = 0.001;
+/// Fastest replay speed
+pub const MAX_SPEED: f64 = 1_000_000.0;
+
+/// Changes of key distances at one point of a session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionFrame {
+    /// Time since the session started
+    pub time: Duration,
+    /// Scan codes whose distance changed and their new distance
+    /// (from 0 to 255, 255 being completely pressed down)
+    pub changes: Vec<(u16, u8)>,
+}
+
+/// A recorded typing session.
+/// Only changes in key distances are stored which keeps the sessions small.
+///
+/// The file format is the magic `RGBS`, a version byte and then every frame as
+/// the time since the previous frame in microseconds, the amount of changes and every change as a
+/// scan code and a distance byte. Numbers other than distances are LEB128 encoded.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub frames: Vec<SessionFrame>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Length of the session.
+    pub fn duration(&self) -> Duration {
+        self.frames
+            .last()
+            .map(|frame| frame.time)
+            .unwrap_or_default()
+    }
+
+    /// Load a session from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Save the session to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Read a session in the session file format.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid_data("Not a session file"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data("Unsupported session file version"));
+        }
+
+        let mut frames = Vec::new();
+        let mut time = Duration::ZERO;
+        loop {
+            // The file may only end between frames
+            let mut first = [0];
+            match reader.read_exact(&mut first) {
+                Ok(()) => (),
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+            let frame = read_frame(&mut reader, first[0], &mut time).map_err(|err| {
+                if err.kind() == io::ErrorKind::UnexpectedEof {
+                    invalid_data("Session file ends in the middle of a frame")
+                } else {
+                    err
+                }
+            })?;
+            frames.push(frame);
+        }
+
+        Ok(Self { frames })
+    }
+
+    /// Write the session in the session file format.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        let mut last = Duration::ZERO;
+        for frame in self.frames.iter() {
+            write_varint(
+                &mut writer,
+                (frame.time.saturating_sub(last)).as_micros() as u64,
+            )?;
+            last = frame.time;
+
+            write_varint(&mut writer, frame.changes.len() as u64)?;
+            for (code, distance) in frame.changes.iter() {
+                write_varint(&mut writer, *code as u64)?;
+                writer.write_all(&[*distance])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records everything read from the wrapped source into a session.
+/// Use the source as you normally would, for example with
+/// [`DeltaWatcher::with_source`](crate::keyboard::DeltaWatcher::with_source).
+pub struct Recorder<S: AnalogSource> {
+    source: S,
+    clock: Clock,
+    start: Option<Instant>,
+    distances: HashMap<u16, u8>,
+    session: Arc<Mutex<Session>>,
+}
+
+impl<S: AnalogSource> Recorder<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            clock: Clock::real(),
+            start: None,
+            distances: HashMap::new(),
+            session: Arc::default(),
+        }
+    }
+
+    /// Timestamp the session with the given clock instead of real time.
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Handle to the session being recorded.
+    /// Save it once you're done recording.
+    pub fn session(&self) -> Arc<Mutex<Session>> {
+        self.session.clone()
+    }
+}
+
+impl<S: AnalogSource> AnalogSource for Recorder<S> {
+    fn init(&mut self) -> Result<(), String> {
+        self.source.init()
+    }
+
+    fn read(&mut self, samples: &mut Vec<(u16, f32)>) -> Result<(), String> {
+        let from = samples.len();
+        self.source.read(samples)?;
+        let start = *self.start.get_or_insert_with(|| self.clock.now());
+
+        let mut changes = Vec::new();
+        for (code, distance) in samples[from..].iter().copied() {
+            let distance = (distance * 255.0).round() as u8;
+            let last = self.distances.insert(code, distance).unwrap_or(0);
+            if last != distance {
+                changes.push((code, distance));
+            }
+        }
+
+        if !changes.is_empty() {
+            self.session.lock().unwrap().frames.push(SessionFrame {
+                // Stored with the same precision as in the file
+                time: Duration::from_micros(self.clock.elapsed(start).as_micros() as u64),
+                changes,
+            });
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.source.close();
+    }
+}
+
+/// Replays a recorded session in real time or sped up.
+pub struct SessionReplay {
+    session: Session,
+    speed: f64,
+    clock: Clock,
+    start: Option<Instant>,
+    next_frame: usize,
+    distances: HashMap<u16, u8>,
+    previous: Vec<u16>,
+}
+
+impl SessionReplay {
+    /// Replay at the speed multiplier, 1 being real time.
+    /// The speed is limited to `MIN_SPEED..=MAX_SPEED`, NaN plays in real time.
+    pub fn new(session: Session, speed: f64) -> Self {
+        Self {
+            session,
+            speed: valid_speed(speed),
+            clock: Clock::real(),
+            start: None,
+            next_frame: 0,
+            distances: HashMap::new(),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Follow the given clock instead of real time.
+    /// With a manual clock the session is replayed exactly as the clock advances.
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Load a session from a file for replaying.
+    pub fn load<P: AsRef<Path>>(path: P, speed: f64) -> io::Result<Self> {
+        Ok(Self::new(Session::load(path)?, speed))
+    }
+
+    /// Playback speed multiplier.
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Change the playback speed, limited like in `new`.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = valid_speed(speed);
+    }
+
+    /// Whether every frame of the session has been replayed.
+    pub fn is_finished(&self) -> bool {
+        self.next_frame >= self.session.frames.len()
+    }
+}
+
+impl AnalogSource for SessionReplay {
+    fn read(&mut self, samples: &mut Vec<(u16, f32)>) -> Result<(), String> {
+        let start = *self.start.get_or_insert_with(|| self.clock.now());
+        let elapsed = self.clock.elapsed(start).mul_f64(self.speed);
+
+        while let Some(frame) = self.session.frames.get(self.next_frame)
+            && frame.time <= elapsed
+        {
+            for (code, distance) in frame.changes.iter().copied() {
+                self.distances.insert(code, distance);
+            }
+            self.next_frame += 1;
+        }
+
+        let from = samples.len();
+        samples.extend(
+            self.distances
+                .iter()
+                .filter(|(_, distance)| **distance > 0)
+                .map(|(code, distance)| (*code, *distance as f32 / 255.0)),
+        );
+        let mut new = samples.split_off(from);
+        release_missing(&mut self.previous, &mut new);
+        samples.append(&mut new);
+        Ok(())
+    }
+}
+
+/// Read the rest of a frame whose first byte has been read already.
+fn read_frame<R: Read>(reader: &mut R, first: u8, time: &mut Duration) -> io::Result<SessionFrame> {
+    let delta = read_varint_from(first, reader)?;
+    *time = time
+        .checked_add(Duration::from_micros(delta))
+        .ok_or_else(|| invalid_data("Session too long"))?;
+
+    let len = read_varint(reader)?;
+    let mut changes = Vec::with_capacity(len.min(256) as usize);
+    for _ in 0..len {
+        let code = u16::try_from(read_varint(reader)?)
+            .map_err(|_| invalid_data("Scan code out of range"))?;
+        let mut distance = [0];
+        reader.read_exact(&mut distance)?;
+        changes.push((code, distance[0]));
+    }
+    Ok(SessionFrame {
+        time: *time,
+        changes,
+    })
+}
+
+fn valid_speed(speed: f64) -> f64 {
+    if speed.is_nan() {
+        1.0
+    } else {
+        speed.clamp(MIN_SPEED, MAX_SPEED)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut first = [0];
+    reader.read_exact(&mut first)?;
+    read_varint_from(first[0], reader)
+}
+
+/// Read a number whose first byte has been read already.
+fn read_varint_from<R: Read>(first: u8, reader: &mut R) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut byte = first;
+    for shift in (0..64).step_by(7) {
+        if shift > 0 {
+            let mut next = [0];
+            reader.read_exact(&mut next)?;
+            byte = next[0];
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("Number too large"))
+}
@@ -1,153 +1,161 @@
-// use crate::keyboard::matrix::*;
+use std::{collections::BTreeMap, path::Path, sync::RwLock};
 
+use crate::keyboard::{Layout, current_layout};
+
+/// How many keys are read from the analog SDK at once
 pub const SCAN_CODE_LEN: usize = 133;
 
-/// matrix position based on key code if any (on my layout)
-pub fn scan_code_to_matrix_pos(scan_code: u16) -> Option<(u8, u8)> {
-    // lol they in a random order
-    // it would suck to debug that trash
-    // Update: The aren't in random order. They are in some actual order.
-    let pos = match scan_code {
-        1 => (1, 0),
-        41 => (1, 0),
-        69 => (1, 0),
-        2 => (1, 1),
-        59 => (1, 1),
-        79 => (1, 1),
-        3 => (1, 2),
-        60 => (1, 2),
-        80 => (1, 2),
-        4 => (1, 3),
-        61 => (1, 3),
-        81 => (1, 3),
-        5 => (1, 4),
-        62 => (1, 4),
-        75 => (1, 4),
-        6 => (1, 5),
-        63 => (1, 5),
-        76 => (1, 5),
-        7 => (1, 6),
-        64 => (1, 6),
-        77 => (1, 6),
-        8 => (1, 7),
-        65 => (1, 7),
-        71 => (1, 7),
-        9 => (1, 8),
-        66 => (1, 8),
-        72 => (1, 8),
-        10 => (1, 9),
-        67 => (1, 9),
-        73 => (1, 9),
-        11 => (1, 10),
-        68 => (1, 10),
-        82 => (1, 10),
-        12 => (1, 11),
-        78 => (1, 11),
-        87 => (1, 11),
-        13 => (1, 12),
-        88 => (1, 12),
-        14 => (1, 13),
-        57427 => (1, 13),
-        15 => (2, 0),
-        57399 => (2, 0),
-        16 => (2, 1),
-        100 => (2, 1),
-        773 => (2, 1),
-        17 => (2, 2),
-        101 => (2, 2),
-        57417 => (2, 2),
-        18 => (2, 3),
-        102 => (2, 3),
-        772 => (2, 3),
-        19 => (2, 4),
-        103 => (2, 4),
-        20 => (2, 5),
-        104 => (2, 5),
-        21 => (2, 6),
-        105 => (2, 6),
-        22 => (2, 7),
-        106 => (2, 7),
-        23 => (2, 8),
-        107 => (2, 8),
-        57426 => (2, 8),
-        24 => (2, 9),
-        108 => (2, 9),
-        25 => (2, 10),
-        109 => (2, 10),
-        1027 => (2, 10),
-        26 => (2, 11),
-        110 => (2, 11),
-        1028 => (2, 11),
-        27 => (2, 12),
-        1029 => (2, 12),
-        57397 => (2, 12),
-        58 => (3, 0),
-        1035 => (3, 0),
-        30 => (3, 1),
-        118 => (3, 1),
-        57415 => (3, 1),
-        31 => (3, 2),
-        57425 => (3, 2),
-        32 => (3, 3),
-        57423 => (3, 3),
-        33 => (3, 4),
-        34 => (3, 5),
-        35 => (3, 6),
-        36 => (3, 7),
-        950 => (3, 7),
-        37 => (3, 8),
-        973 => (3, 8),
-        38 => (3, 9),
-        949 => (3, 9),
-        39 => (3, 10),
-        1026 => (3, 10),
-        40 => (3, 11),
-        1025 => (3, 11),
-        43 => (3, 12),
-        55 => (3, 12),
-        1037 => (3, 12),
-        28 => (3, 13),
-        1032 => (3, 13),
-        57372 => (3, 13),
-        42 => (4, 0),
-        86 => (4, 1),
-        44 => (4, 2),
-        45 => (4, 3),
-        46 => (4, 4),
-        47 => (4, 5),
-        48 => (4, 6),
-        49 => (4, 7),
-        50 => (4, 8),
-        994 => (4, 8),
-        51 => (4, 9),
-        1002 => (4, 9),
-        52 => (4, 10),
-        83 => (4, 10),
-        1001 => (4, 10),
-        53 => (4, 11),
-        74 => (4, 11),
-        57416 => (4, 11),
-        54 => (4, 13),
-        1036 => (4, 13),
-        29 => (5, 0),
-        1034 => (5, 1),
-        57435 => (5, 1),
-        56 => (5, 2),
-        57 => (5, 6),
-        57400 => (5, 10),
-        57419 => (5, 10),
-        57424 => (5, 11),
-        57437 => (5, 11),
-        1033 => (5, 13),
-        57373 => (5, 12),
-        57421 => (5, 12),
-        _ => return None,
-    };
+/// Translation the delta watcher uses. None means the one of the current layout.
+static CURRENT: RwLock<Option<Translation>> = RwLock::new(None);
+
+/// Kind of key codes the analog source reports.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum KeycodeType {
+    #[default]
+    ScanCode1,
+    /// USB HID usage codes
+    Hid,
+}
+
+/// Translates key codes from the analog source to matrix positions.
+/// Several codes can point to the same key.
+///
+/// Can be loaded from a text file:
+/// ```text
+/// # code row column
+/// type scancode1
+/// 1 1 0
+/// 57427 1 13
+/// ```
+/// `type` is `scancode1` (default) or `hid`. `#` starts a comment.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Translation {
+    pub keycode_type: KeycodeType,
+    codes: BTreeMap<u16, (u8, u8)>,
+}
+
+impl Translation {
+    pub fn new(keycode_type: KeycodeType) -> Self {
+        Self {
+            keycode_type,
+            codes: BTreeMap::new(),
+        }
+    }
+
+    /// Translation from the scan codes of the layout.
+    /// HID codes are converted from them, keys without a HID code are left out.
+    pub fn from_layout(layout: &Layout, keycode_type: KeycodeType) -> Self {
+        let mut translation = Self::new(keycode_type);
+        let iso = layout.is_iso();
+        for key in layout.keys.iter() {
+            for scan_code in key.scan_codes.iter().copied() {
+                let code = match keycode_type {
+                    KeycodeType::ScanCode1 => Some(scan_code),
+                    // The same scan code is a different HID key on ISO boards
+                    KeycodeType::Hid if iso && scan_code == 43 => Some(50),
+                    KeycodeType::Hid => scan_code1_to_hid(scan_code),
+                };
+                if let Some(code) = code {
+                    translation.insert(code, key.matrix);
+                }
+            }
+        }
+        translation
+    }
+
+    /// Load a translation from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse(&text)
+    }
+
+    /// Parse a translation from text.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut translation = Self::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |msg: &str| format!("Line {}: {msg}", i + 1);
+
+            if let Some(keycode_type) = line.strip_prefix("type ") {
+                translation.keycode_type = match keycode_type.trim() {
+                    "scancode1" => KeycodeType::ScanCode1,
+                    "hid" => KeycodeType::Hid,
+                    other => return Err(error(&format!("Unknown key code type `{other}`"))),
+                };
+                continue;
+            }
+
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            let [code, row, column] = parts[..] else {
+                return Err(error("Expected code row column"));
+            };
+            let code = code.parse::<u16>().map_err(|err| error(&err.to_string()))?;
+            let row = row.parse::<u8>().map_err(|err| error(&err.to_string()))?;
+            let column = column
+                .parse::<u8>()
+                .map_err(|err| error(&err.to_string()))?;
+            translation.insert(code, (row, column));
+        }
+
+        Ok(translation)
+    }
 
-    Some(pos)
+    /// Map the code to the matrix position.
+    /// Replaces the previous position of the code.
+    pub fn insert(&mut self, code: u16, matrix: (u8, u8)) {
+        self.codes.insert(code, matrix);
+    }
+
+    pub fn remove(&mut self, code: u16) -> Option<(u8, u8)> {
+        self.codes.remove(&code)
+    }
+
+    /// Matrix position of the code if it is mapped.
+    pub fn get(&self, code: u16) -> Option<(u8, u8)> {
+        self.codes.get(&code).copied()
+    }
+
+    /// Every mapped code and its matrix position ordered by code.
+    pub fn codes(&self) -> impl Iterator<Item = (u16, (u8, u8))> + '_ {
+        self.codes.iter().map(|(code, matrix)| (*code, *matrix))
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+}
+
+/// Use the translation for delta watchers created after this.
+pub fn set_translation(translation: Translation) {
+    *CURRENT.write().unwrap() = Some(translation);
+}
+
+/// Translation new delta watchers use.
+/// Unless one is set, this is the ScanCode1 translation of the current layout.
+pub fn current_translation() -> Translation {
+    CURRENT
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| Translation::from_layout(&current_layout(), KeycodeType::ScanCode1))
+}
+
+/// Matrix position of the scan code in the current translation.
+#[deprecated(note = "use `current_translation().get(scan_code)` instead")]
+pub fn scan_code_to_matrix_pos(scan_code: u16) -> Option<(u8, u8)> {
+    current_translation().get(scan_code)
 }
 
 /// Wooting 60HE scan codes I checked manually from my keyboard
+#[deprecated(note = "use the codes of `current_translation()` instead")]
 pub const SCAN_CODES: [u16; SCAN_CODE_LEN] = [
     1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
     27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50,
@@ -157,3 +165,100 @@ pub const SCAN_CODES: [u16; SCAN_CODE_LEN] = [
     1034, 1035, 1036, 1037, 57372, 57373, 57397, 57399, 57400, 57415, 57416, 57417, 57419, 57421,
     57423, 57424, 57425, 57426, 57427, 57435, 57437,
 ];
+
+/// HID usage code of the key with the ScanCode1 code.
+pub fn scan_code1_to_hid(scan_code: u16) -> Option<u16> {
+    let hid = match scan_code {
+        1 => 41,
+        2..=10 => scan_code + 28,
+        11 => 39,
+        12 => 45,
+        13 => 46,
+        14 => 42,
+        15 => 43,
+        16 => 20,
+        17 => 26,
+        18 => 8,
+        19 => 21,
+        20 => 23,
+        21 => 28,
+        22 => 24,
+        23 => 12,
+        24 => 18,
+        25 => 19,
+        26 => 47,
+        27 => 48,
+        28 => 40,
+        29 => 224,
+        30 => 4,
+        31 => 22,
+        32 => 7,
+        33 => 9,
+        34 => 10,
+        35 => 11,
+        36 => 13,
+        37 => 14,
+        38 => 15,
+        39 => 51,
+        40 => 52,
+        41 => 53,
+        42 => 225,
+        // Backslash on ANSI boards, `Translation::from_layout` uses 50 (Non-US #) on ISO ones
+        43 => 49,
+        44 => 29,
+        45 => 27,
+        46 => 6,
+        47 => 25,
+        48 => 5,
+        49 => 17,
+        50 => 16,
+        51 => 54,
+        52 => 55,
+        53 => 56,
+        54 => 229,
+        55 => 85,
+        56 => 226,
+        57 => 44,
+        58 => 57,
+        59..=68 => scan_code - 1,
+        69 => 83,
+        70 => 71,
+        71 => 95,
+        72 => 96,
+        73 => 97,
+        74 => 86,
+        75 => 92,
+        76 => 93,
+        77 => 94,
+        78 => 87,
+        79 => 89,
+        80 => 90,
+        81 => 91,
+        82 => 98,
+        83 => 99,
+        86 => 100,
+        87 => 68,
+        88 => 69,
+        // Extended codes (0xE0 prefix)
+        0xE01C => 88,
+        0xE01D => 228,
+        0xE035 => 84,
+        0xE037 => 70,
+        0xE038 => 230,
+        0xE047 => 74,
+        0xE048 => 82,
+        0xE049 => 75,
+        0xE04B => 80,
+        0xE04D => 79,
+        0xE04F => 77,
+        0xE050 => 81,
+        0xE051 => 78,
+        0xE052 => 73,
+        0xE053 => 76,
+        0xE05B => 227,
+        0xE05C => 231,
+        0xE05D => 101,
+        _ => return None,
+    };
+    Some(hid)
+}
@@ -0,0 +1,32 @@
+use crate::keyboard::KeyboardMatrix;
+
+/// A layer of keys in the runtime.
+/// Layers are composited on top of each other in the order of their z-index.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub matrix: KeyboardMatrix,
+    /// Hidden layers are skipped when rendering.
+    pub visible: bool,
+    /// How much of this layer is applied on top of the layers below it. (from 0 to 1)
+    pub opacity: f64,
+    /// Name you can look this layer up with.
+    pub name: Option<String>,
+}
+
+impl Layer {
+    pub fn new(matrix: KeyboardMatrix) -> Self {
+        Self {
+            matrix,
+            visible: true,
+            opacity: 1.0,
+            name: None,
+        }
+    }
+
+    pub fn named(name: &str, matrix: KeyboardMatrix) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..Self::new(matrix)
+        }
+    }
+}
@@ -1,22 +1,32 @@
+mod blend;
 mod bounds;
+mod clock;
 mod effect;
 mod key;
+mod layer;
 mod process;
+mod scheduler;
 mod timer;
 
 /// Some random built-in effects you can use to stack to your process.
 pub mod effects;
 /// All core keyboard communication components.
 pub mod keyboard;
+/// Output backends the runtime renders frames to.
+pub mod output;
 
-pub use effect::Effect;
+pub use effect::{Coordinates, Effect};
 
 pub use bounds::Bounds;
 
 /// Everything related to core runtime and process logic.
 pub mod runtime {
+    pub use crate::blend::*;
+    pub use crate::clock::*;
     pub use crate::key::*;
+    pub use crate::layer::*;
     pub use crate::process::*;
+    pub use crate::scheduler::*;
     pub use crate::timer::*;
 }
 
@@ -0,0 +1,117 @@
+use wooting_rgb::RgbKeyboard;
+
+/// Color of a single key in a frame.
+/// Matrix position of the key and the color as 8 bit channels.
+pub type KeyColor = ((u8, u8), (u8, u8, u8));
+
+/// What an output backend can do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    /// Rows in the device matrix
+    pub rows: usize,
+    /// Columns in the device matrix
+    pub columns: usize,
+    /// How many flushes per second the device can handle.
+    /// None if there is no limit.
+    pub max_update_rate: Option<f64>,
+}
+
+/// Something the runtime can send rendered frames to.
+/// Implement this to drive a process against something else than a Wooting keyboard.
+pub trait OutputBackend: Send {
+    /// Whether the device is there to receive frames.
+    fn is_connected(&self) -> bool;
+    /// Set colors of a frame.
+    /// Keys that are not included keep their previous color.
+    /// Nothing is shown before `flush` is called.
+    fn set_frame(&mut self, frame: &[KeyColor]);
+    /// Send the current frame to the device.
+    fn flush(&mut self);
+    /// Capabilities of the device.
+    fn capabilities(&self) -> Capabilities;
+}
+
+impl<B: OutputBackend + ?Sized> OutputBackend for Box<B> {
+    fn is_connected(&self) -> bool {
+        (**self).is_connected()
+    }
+
+    fn set_frame(&mut self, frame: &[KeyColor]) {
+        (**self).set_frame(frame);
+    }
+
+    fn flush(&mut self) {
+        (**self).flush();
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        (**self).capabilities()
+    }
+}
+
+/// Wooting keyboards through the Wooting RGB SDK.
+pub struct WootingBackend {
+    keyboard: RgbKeyboard,
+}
+
+impl WootingBackend {
+    pub fn new() -> Self {
+        Self {
+            keyboard: RgbKeyboard,
+        }
+    }
+}
+
+impl Default for WootingBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputBackend for WootingBackend {
+    fn is_connected(&self) -> bool {
+        wooting_rgb::is_wooting_keyboard_connected()
+    }
+
+    fn set_frame(&mut self, frame: &[KeyColor]) {
+        for (key, (red, green, blue)) in frame.iter().copied() {
+            self.keyboard.array_set_single(key, red, green, blue);
+        }
+    }
+
+    fn flush(&mut self) {
+        self.keyboard.array_update();
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            rows: 6,
+            columns: 21,
+            // Updating the keyboard takes around 16 ms
+            max_update_rate: Some(60.0),
+        }
+    }
+}
+
+/// Backend that throws every frame away.
+/// Useful for running effects without any device.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullBackend;
+
+impl OutputBackend for NullBackend {
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn set_frame(&mut self, _frame: &[KeyColor]) {}
+
+    fn flush(&mut self) {}
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            rows: 6,
+            columns: 21,
+            max_update_rate: None,
+        }
+    }
+}
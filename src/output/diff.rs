@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use crate::output::KeyColor;
+
+/// How much keyboard traffic was avoided by skipping unchanged keys.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OutputStats {
+    /// Frames flushed to the backend
+    pub frames_flushed: u64,
+    /// Frames that were not flushed since nothing changed
+    pub frames_skipped: u64,
+    /// Keys sent to the backend
+    pub keys_written: u64,
+    /// Keys that were not sent since they had not changed
+    pub keys_skipped: u64,
+}
+
+/// Remembers the last sent color of every key so only changed keys are sent again.
+#[derive(Debug, Default, Clone)]
+pub struct FrameDiff {
+    last: HashMap<(u8, u8), (u8, u8, u8)>,
+    pub stats: OutputStats,
+}
+
+impl FrameDiff {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keys of the frame that changed since the last one.
+    /// These are remembered as sent.
+    pub fn changes(&mut self, frame: &[KeyColor]) -> Vec<KeyColor> {
+        let changes = frame
+            .iter()
+            .copied()
+            .filter(|(key, color)| self.last.insert(*key, *color) != Some(*color))
+            .collect::<Vec<_>>();
+
+        self.stats.keys_written += changes.len() as u64;
+        self.stats.keys_skipped += (frame.len() - changes.len()) as u64;
+        if changes.is_empty() {
+            self.stats.frames_skipped += 1;
+        } else {
+            self.stats.frames_flushed += 1;
+        }
+        changes
+    }
+
+    /// Count a frame that was sent completely.
+    pub fn record_full(&mut self, frame: &[KeyColor]) {
+        for (key, color) in frame.iter().copied() {
+            self.last.insert(key, color);
+        }
+        self.stats.keys_written += frame.len() as u64;
+        self.stats.frames_flushed += 1;
+    }
+
+    /// Forget the sent colors so the next frame is sent completely.
+    pub fn reset(&mut self) {
+        self.last.clear();
+    }
+}
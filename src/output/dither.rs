@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+use palette::Srgb;
+
+/// Temporal dithering of the 8 bit output.
+/// The part of every channel that is lost to rounding is carried over to the next frame of the
+/// key, so over a few frames the average matches the real color.
+/// Makes slow fades and dim colors look smooth instead of stepping.
+#[derive(Debug, Default, Clone)]
+pub struct Dither {
+    residuals: HashMap<(u8, u8), [f64; 3]>,
+}
+
+impl Dither {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Quantize the color of the key (0 to 1 per channel) to 8 bits.
+    pub fn quantize(&mut self, key: (u8, u8), color: Srgb<f64>) -> (u8, u8, u8) {
+        let residual = self.residuals.entry(key).or_default();
+        let mut channel = |i: usize, c: f64| {
+            let value = c * 255.0 + residual[i];
+            let quantized = value.round().clamp(0.0, 255.0);
+            // Keep the error small so clamped colors don't build it up forever
+            residual[i] = (value - quantized).clamp(-1.0, 1.0);
+            quantized as u8
+        };
+        (
+            channel(0, color.red),
+            channel(1, color.green),
+            channel(2, color.blue),
+        )
+    }
+
+    /// Forget the carried over errors.
+    pub fn reset(&mut self) {
+        self.residuals.clear();
+    }
+}
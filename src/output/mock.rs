@@ -0,0 +1,122 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::Clock,
+    output::{Capabilities, KeyColor, OutputBackend},
+};
+
+/// A frame that was flushed to a [`MockBackend`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    /// Time since the backend was created according to its clock
+    pub time: Duration,
+    /// Every key that has been set so far, sorted by matrix position
+    pub keys: Vec<KeyColor>,
+}
+
+impl RecordedFrame {
+    /// Color of the key in this frame if it has been set.
+    pub fn color(&self, key: (u8, u8)) -> Option<(u8, u8, u8)> {
+        self.keys
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, color)| *color)
+    }
+}
+
+/// In-memory backend which records every flushed frame.
+/// Clones share the recorded frames, so keep a clone around to inspect what the runtime rendered.
+#[derive(Debug, Clone)]
+pub struct MockBackend {
+    clock: Clock,
+    start: Instant,
+    current: Vec<KeyColor>,
+    frames: Arc<Mutex<Vec<RecordedFrame>>>,
+    capabilities: Capabilities,
+}
+
+impl MockBackend {
+    /// Mock that timestamps frames with real time.
+    pub fn new() -> Self {
+        Self::with_clock(Clock::real())
+    }
+
+    /// Mock that timestamps frames with the clock.
+    /// Give it a clone of the manual clock of the runtime to get the same frame times on every run.
+    pub fn with_clock(clock: Clock) -> Self {
+        Self {
+            start: clock.now(),
+            clock,
+            current: Vec::new(),
+            frames: Arc::default(),
+            capabilities: Capabilities {
+                rows: 6,
+                columns: 21,
+                max_update_rate: None,
+            },
+        }
+    }
+
+    /// Mock that reports the given capabilities and timestamps frames with the clock.
+    pub fn with_capabilities(clock: Clock, capabilities: Capabilities) -> Self {
+        Self {
+            capabilities,
+            ..Self::with_clock(clock)
+        }
+    }
+
+    /// Every frame flushed so far.
+    pub fn frames(&self) -> Vec<RecordedFrame> {
+        self.frames.lock().unwrap().clone()
+    }
+
+    /// The latest flushed frame.
+    pub fn last_frame(&self) -> Option<RecordedFrame> {
+        self.frames.lock().unwrap().last().cloned()
+    }
+
+    /// How many frames have been flushed.
+    pub fn frame_count(&self) -> usize {
+        self.frames.lock().unwrap().len()
+    }
+
+    /// Forget every recorded frame.
+    pub fn clear(&self) {
+        self.frames.lock().unwrap().clear();
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputBackend for MockBackend {
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn set_frame(&mut self, frame: &[KeyColor]) {
+        for (key, color) in frame.iter().copied() {
+            match self.current.binary_search_by_key(&key, |(k, _)| *k) {
+                Ok(i) => self.current[i].1 = color,
+                Err(i) => self.current.insert(i, (key, color)),
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        self.frames.lock().unwrap().push(RecordedFrame {
+            time: self.clock.elapsed(self.start),
+            keys: self.current.clone(),
+        });
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+}
@@ -0,0 +1,13 @@
+mod backend;
+mod diff;
+mod dither;
+mod mock;
+mod threaded;
+mod transfer;
+
+pub use backend::*;
+pub use diff::*;
+pub use dither::*;
+pub use mock::*;
+pub use threaded::*;
+pub use transfer::*;
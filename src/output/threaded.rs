@@ -0,0 +1,137 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicU64, Ordering::Relaxed},
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::output::{Capabilities, KeyColor, OutputBackend};
+
+#[derive(Debug, Default)]
+struct Pending {
+    keys: Vec<KeyColor>,
+    /// Where each key is in `keys`
+    index: HashMap<(u8, u8), usize>,
+    ready: bool,
+    exit: bool,
+}
+
+/// Hands frames to a background thread which writes them to the wrapped backend.
+/// Flushing never blocks, if the writer is still busy the newest frame replaces the waiting one.
+pub struct ThreadedBackend {
+    pending: Arc<(Mutex<Pending>, Condvar)>,
+    /// Frames that were replaced before the writer got to them
+    dropped: Arc<AtomicU64>,
+    /// Frames the writer has sent to the device
+    written: Arc<AtomicU64>,
+    connected: bool,
+    capabilities: Capabilities,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl ThreadedBackend {
+    /// Move the backend to a new writer thread.
+    pub fn new<B: OutputBackend + 'static>(mut backend: B) -> Self {
+        let pending: Arc<(Mutex<Pending>, Condvar)> = Arc::default();
+        let written: Arc<AtomicU64> = Arc::default();
+        let connected = backend.is_connected();
+        let capabilities = backend.capabilities();
+
+        let writer = {
+            let pending = pending.clone();
+            let written = written.clone();
+            thread::spawn(move || {
+                let mut frame = Vec::new();
+                loop {
+                    {
+                        let (lock, condvar) = &*pending;
+                        let mut pending = condvar
+                            .wait_while(lock.lock().unwrap(), |p| !p.ready && !p.exit)
+                            .unwrap();
+                        if !pending.ready {
+                            break;
+                        }
+                        pending.ready = false;
+                        std::mem::swap(&mut frame, &mut pending.keys);
+                        pending.keys.clear();
+                        pending.index.clear();
+                    }
+
+                    backend.set_frame(&frame);
+                    backend.flush();
+                    written.fetch_add(1, Relaxed);
+                }
+            })
+        };
+
+        Self {
+            pending,
+            dropped: Arc::default(),
+            written,
+            connected,
+            capabilities,
+            writer: Some(writer),
+        }
+    }
+
+    /// How many frames were replaced by a newer one before they were written.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped.load(Relaxed)
+    }
+
+    /// How many frames have been written to the device.
+    pub fn written_frames(&self) -> u64 {
+        self.written.load(Relaxed)
+    }
+}
+
+impl OutputBackend for ThreadedBackend {
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn set_frame(&mut self, frame: &[KeyColor]) {
+        let mut pending = self.pending.0.lock().unwrap();
+        let Pending { keys, index, .. } = &mut *pending;
+        // Keys of a frame that is still waiting are kept so partial frames don't get lost
+        for (key, color) in frame.iter().copied() {
+            match index.get(&key) {
+                Some(i) => keys[*i].1 = color,
+                None => {
+                    index.insert(key, keys.len());
+                    keys.push((key, color));
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let (lock, condvar) = &*self.pending;
+        let mut pending = lock.lock().unwrap();
+        if pending.ready {
+            self.dropped.fetch_add(1, Relaxed);
+        }
+        pending.ready = true;
+        condvar.notify_one();
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+}
+
+impl Drop for ThreadedBackend {
+    /// Writes the last frame and stops the writer.
+    fn drop(&mut self) {
+        {
+            let (lock, condvar) = &*self.pending;
+            lock.lock().unwrap().exit = true;
+            condvar.notify_one();
+        }
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
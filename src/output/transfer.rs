@@ -0,0 +1,101 @@
+use palette::{LinSrgb, Srgb};
+
+/// What to do with colors that are out of the 0 to 1 range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClampPolicy {
+    /// Every channel is clamped on its own.
+    /// Bright colors saturate towards white.
+    #[default]
+    Clamp,
+    /// Negative channels are clamped and the color is scaled down until its brightest channel is 1.
+    /// Keeps the hue of bright colors.
+    Scale,
+}
+
+/// How a rendered color is turned into what is sent to the LEDs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputTransfer {
+    /// Exponent applied to every channel.
+    /// LEDs are linear, so around 2.2 makes sRGB colors look like they do on a screen.
+    pub gamma: f64,
+    /// Multiplier of each channel for correcting the tint of the LEDs
+    pub white_balance: Srgb<f64>,
+    /// Global brightness from 0 to 1, multiplies every channel after the gamma curve.
+    /// Values outside of 0 to 1 are clamped.
+    pub brightness: f64,
+    pub clamp: ClampPolicy,
+}
+
+impl Default for OutputTransfer {
+    /// Sends colors as they are, only clamping them.
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            white_balance: Srgb::new(1.0, 1.0, 1.0),
+            brightness: 1.0,
+            clamp: ClampPolicy::Clamp,
+        }
+    }
+}
+
+impl OutputTransfer {
+    /// Gamma curve that matches sRGB colors to the linear LEDs
+    pub fn led() -> Self {
+        Self {
+            gamma: 2.2,
+            ..Default::default()
+        }
+    }
+
+    /// Color in the range of 0 to 1 that should be shown on the LED.
+    pub fn apply(&self, color: Srgb<f64>) -> Srgb<f64> {
+        let color = match self.clamp {
+            ClampPolicy::Clamp => Srgb::new(
+                color.red.clamp(0.0, 1.0),
+                color.green.clamp(0.0, 1.0),
+                color.blue.clamp(0.0, 1.0),
+            ),
+            ClampPolicy::Scale => {
+                let color = Srgb::new(
+                    color.red.max(0.0),
+                    color.green.max(0.0),
+                    color.blue.max(0.0),
+                );
+                let max = color.red.max(color.green).max(color.blue);
+                if max > 1.0 { color / max } else { color }
+            }
+        };
+        let brightness = self.brightness.clamp(0.0, 1.0);
+        let channel = |c: f64, balance: f64| {
+            // NaN would turn into 0 anyway, but be explicit about it
+            let c = if c.is_finite() { c } else { 0.0 };
+            (c.powf(self.gamma) * balance * brightness).clamp(0.0, 1.0)
+        };
+        Srgb::new(
+            channel(color.red, self.white_balance.red),
+            channel(color.green, self.white_balance.green),
+            channel(color.blue, self.white_balance.blue),
+        )
+    }
+
+    /// Color of the LED with 8 bits per channel.
+    pub fn to_rgb8(&self, color: Srgb<f64>) -> (u8, u8, u8) {
+        let color = self.apply(color);
+        (
+            (color.red * 255.0).round() as u8,
+            (color.green * 255.0).round() as u8,
+            (color.blue * 255.0).round() as u8,
+        )
+    }
+}
+
+/// Channels of the color in linear light, still stored as `Srgb` for blending.
+pub(crate) fn to_linear(color: Srgb<f64>) -> Srgb<f64> {
+    let linear: LinSrgb<f64> = color.into_linear();
+    Srgb::new(linear.red, linear.green, linear.blue)
+}
+
+/// Opposite of `to_linear`.
+pub(crate) fn from_linear(color: Srgb<f64>) -> Srgb<f64> {
+    Srgb::from_linear(LinSrgb::new(color.red, color.green, color.blue))
+}
@@ -9,11 +9,19 @@ use std::{
 };
 
 use tween::{Tween, Tweener};
-use wooting_rgb::RgbKeyboard;
 
 use crate::{
+    blend::BlendMode,
+    clock::Clock,
+    effect::{Coordinates, Effect, EffectBinding},
     key::ColorBlendTypes,
-    keyboard::{DeltaWatcher, KeyboardMatrix, get_matrix},
+    keyboard::{DeltaWatcher, KeyEvent, KeyboardMatrix, get_matrix},
+    layer::Layer,
+    output::{
+        Dither, FrameDiff, KeyColor, NullBackend, OutputBackend, OutputStats, OutputTransfer,
+        ThreadedBackend, WootingBackend, from_linear, to_linear,
+    },
+    scheduler::Scheduler,
     timer::Timer,
 };
 
@@ -27,114 +35,364 @@ pub type StandardTweenerData<T> = (
 );
 
 pub struct Runtime<T: Process<Owner = Self>> {
-    pub keyboard: RgbKeyboard,
+    /// Where the rendered frames are sent to
+    pub backend: Box<dyn OutputBackend>,
     /// Since the runtime started
     pub start: Instant,
+    /// Clock the runtime, its timers and tweeners follow.
+    /// Set this to a manual clock before running to step time yourself.
+    pub clock: Clock,
+    /// Paces the process loop and rendering
+    pub scheduler: Scheduler,
+    /// Only send keys that changed since the last update to the keyboard.
+    /// Frames where nothing changed are not sent at all.
+    pub skip_unchanged: bool,
+    frame_diff: FrameDiff,
+    /// Blend layers in linear light instead of directly in sRGB.
+    /// Gives physically correct mixing, so additive layers don't wash out as fast.
+    pub linear_compositing: bool,
+    /// Gamma, white balance and brightness applied to the final colors before they are sent
+    pub transfer: OutputTransfer,
+    /// Carry the rounding error of every key over to the next frames.
+    /// Smooths out slow fades and dim colors.
+    pub dithering: bool,
+    dither: Dither,
     /// Delta watcher you can copy for other things
     pub delta_watcher: Option<DeltaWatcher>,
     /// Delta since last frame.
     /// This is for when you cannot access it from process. Like in a timer or tweener.
     pub delta: Duration,
     exit: bool,
+    last: Instant,
     tweeners: Vec<StandardTweenerData<T>>,
     timers: Vec<Rc<RefCell<Timer<T>>>>,
-    effect_layers: HashMap<i32, KeyboardMatrix>,
+    effect_layers: HashMap<i32, Layer>,
+    effect_bindings: HashMap<i32, EffectBinding>,
+    blend_modes: HashMap<u32, Box<dyn BlendMode>>,
     render_layer: KeyboardMatrix,
 }
 
 impl<T: Process<Owner = Self>> Runtime<T> {
-    /// Create a new runtime
+    /// Create a new runtime which renders to a Wooting keyboard
     pub fn new(analog: bool) -> Self {
+        Self::with_backend(analog, WootingBackend::new())
+    }
+
+    /// Create a new runtime which renders to the given backend
+    pub fn with_backend<B: OutputBackend + 'static>(analog: bool, backend: B) -> Self {
+        let delta_watcher = if analog {
+            Some(DeltaWatcher::new(Duration::from_millis(1), 10, 255 / 2))
+        } else {
+            None
+        };
+        Self::with_delta_watcher(backend, delta_watcher)
+    }
+
+    /// Create a new runtime with the given backend and delta watcher.
+    /// Use this with a [`MockBackend`](crate::output::MockBackend) and a polled delta watcher
+    /// to run a process without any hardware.
+    pub fn with_delta_watcher<B: OutputBackend + 'static>(
+        backend: B,
+        delta_watcher: Option<DeltaWatcher>,
+    ) -> Self {
         Self {
-            delta_watcher: if analog {
-                Some(DeltaWatcher::new(Duration::from_millis(1), 10, 255 / 2))
-            } else {
-                None
-            },
+            delta_watcher,
             exit: false,
             tweeners: Vec::new(),
             timers: Vec::new(),
             start: Instant::now(),
+            clock: Clock::real(),
+            scheduler: Scheduler::default(),
+            skip_unchanged: true,
+            frame_diff: FrameDiff::new(),
+            linear_compositing: false,
+            transfer: OutputTransfer::default(),
+            dithering: false,
+            dither: Dither::new(),
+            last: Instant::now(),
             effect_layers: HashMap::new(),
-            keyboard: RgbKeyboard,
+            effect_bindings: HashMap::new(),
+            blend_modes: HashMap::new(),
+            backend: Box::new(backend),
             delta: Duration::ZERO,
             render_layer: get_matrix(),
         }
     }
 
+    /// Move keyboard communication to a background thread.
+    /// `update_keyboard` then only hands the frame over and returns immediately.
+    /// If frames are produced faster than the keyboard takes them, only the newest one is sent.
+    pub fn render_in_background(&mut self) {
+        let backend = std::mem::replace(&mut self.backend, Box::new(NullBackend));
+        self.backend = Box::new(ThreadedBackend::new(backend));
+    }
+
     /// Create a layer you can use for effects.
+    /// Replaces the layer that was at the z_index.
     pub fn create_layer(&mut self, z_index: i32, layer: KeyboardMatrix) {
-        self.effect_layers.insert(z_index, layer);
+        self.effect_layers.insert(z_index, Layer::new(layer));
+    }
+
+    /// Create a layer that can also be looked up by name.
+    /// Replaces the layer that was at the z_index.
+    /// Fails if another z_index already has a layer with the name.
+    pub fn create_named_layer(
+        &mut self,
+        name: &str,
+        z_index: i32,
+        layer: KeyboardMatrix,
+    ) -> Result<(), String> {
+        if let Some(existing) = self.layer_index(name)
+            && existing != z_index
+        {
+            return Err(format!(
+                "Layer name `{name}` is already used by z_index {existing}"
+            ));
+        }
+        self.effect_layers
+            .insert(z_index, Layer::named(name, layer));
+        Ok(())
     }
 
     /// # Panics
     /// If the z_index does not exist.
     pub fn get_layer(&mut self, z_index: i32) -> &mut KeyboardMatrix {
-        self.effect_layers.get_mut(&z_index).unwrap()
+        &mut self.effect_layers.get_mut(&z_index).unwrap().matrix
     }
 
-    /// Run the process loop
-    pub fn run(&mut self, process: &mut T) {
-        assert!(
-            wooting_rgb::is_wooting_keyboard_connected(),
-            "Wooting keyboard not connected"
+    /// Keys of the layer if it exists.
+    pub fn try_get_layer(&mut self, z_index: i32) -> Option<&mut KeyboardMatrix> {
+        self.effect_layers
+            .get_mut(&z_index)
+            .map(|layer| &mut layer.matrix)
+    }
+
+    /// Keys of the layer with the given name if it exists.
+    pub fn get_layer_by_name(&mut self, name: &str) -> Option<&mut KeyboardMatrix> {
+        let z_index = self.layer_index(name)?;
+        self.try_get_layer(z_index)
+    }
+
+    /// The layer with its settings if it exists.
+    pub fn layer(&self, z_index: i32) -> Option<&Layer> {
+        self.effect_layers.get(&z_index)
+    }
+
+    /// The layer with its settings if it exists.
+    pub fn layer_mut(&mut self, z_index: i32) -> Option<&mut Layer> {
+        self.effect_layers.get_mut(&z_index)
+    }
+
+    /// Whether there is a layer at the z_index.
+    pub fn has_layer(&self, z_index: i32) -> bool {
+        self.effect_layers.contains_key(&z_index)
+    }
+
+    /// z_index of the layer with the given name.
+    /// If names were changed to be the same, the lowest z_index is returned.
+    pub fn layer_index(&self, name: &str) -> Option<i32> {
+        self.effect_layers
+            .iter()
+            .filter(|(_, layer)| layer.name.as_deref() == Some(name))
+            .map(|(z_index, _)| *z_index)
+            .min()
+    }
+
+    /// Every z_index that has a layer from bottom to top.
+    pub fn layer_indices(&self) -> Vec<i32> {
+        let mut indices = self.effect_layers.keys().copied().collect::<Vec<_>>();
+        indices.sort();
+        indices
+    }
+
+    /// Remove the layer and return it.
+    /// An effect bound to it is removed as well.
+    pub fn remove_layer(&mut self, z_index: i32) -> Option<Layer> {
+        self.effect_bindings.remove(&z_index);
+        self.effect_layers.remove(&z_index)
+    }
+
+    /// Show or hide the layer.
+    /// Does nothing if the layer does not exist.
+    pub fn set_layer_visible(&mut self, z_index: i32, visible: bool) {
+        if let Some(layer) = self.effect_layers.get_mut(&z_index) {
+            layer.visible = visible;
+        }
+    }
+
+    /// Set how much of the layer is applied on top of the layers below it. (from 0 to 1)
+    /// Does nothing if the layer does not exist.
+    pub fn set_layer_opacity(&mut self, z_index: i32, opacity: f64) {
+        if let Some(layer) = self.effect_layers.get_mut(&z_index) {
+            layer.opacity = opacity.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Move a layer to another z_index.
+    /// If there already is a layer at the target, the two layers swap places.
+    /// Returns false if there is no layer to move.
+    pub fn move_layer(&mut self, from: i32, to: i32) -> bool {
+        let Some(layer) = self.effect_layers.remove(&from) else {
+            return false;
+        };
+        if let Some(other) = self.effect_layers.insert(to, layer) {
+            self.effect_layers.insert(from, other);
+        }
+
+        let binding = self.effect_bindings.remove(&from);
+        if let Some(other) = self.effect_bindings.remove(&to) {
+            self.effect_bindings.insert(from, other);
+        }
+        if let Some(binding) = binding {
+            self.effect_bindings.insert(to, binding);
+        }
+        true
+    }
+
+    /// Let the runtime render the effect to the layer.
+    /// Every key of the layer gets its color from the effect whenever the keyboard is updated
+    /// and the effect is updated on every loop.
+    /// The layer is created if it does not exist yet.
+    /// Replaces the effect that was bound to the layer.
+    pub fn bind_effect<E: Effect + 'static>(
+        &mut self,
+        z_index: i32,
+        effect: E,
+        coordinates: Coordinates,
+    ) {
+        if !self.has_layer(z_index) {
+            self.create_layer(z_index, get_matrix());
+        }
+        self.effect_bindings.insert(
+            z_index,
+            EffectBinding {
+                effect: Box::new(effect),
+                coordinates,
+                supersampling: 1,
+            },
         );
+    }
+
+    /// Evaluate the effect bound to the layer at `samples` x `samples` points over the area of
+    /// every key and use their average, so large keys show the average of the effect under them.
+    /// 1 (the default) only uses the centre of the key.
+    pub fn set_supersampling(&mut self, z_index: i32, samples: u32) {
+        if let Some(binding) = self.effect_bindings.get_mut(&z_index) {
+            binding.supersampling = samples.max(1);
+        }
+    }
+
+    /// Register a blend mode keys can use with `ColorBlendTypes::Custom(id)`.
+    /// Replaces the blend mode that had the same id.
+    pub fn register_blend_mode<B: BlendMode + 'static>(&mut self, id: u32, blend_mode: B) {
+        self.blend_modes.insert(id, Box::new(blend_mode));
+    }
+
+    /// Stop rendering the effect bound to the layer and return it.
+    /// The layer keeps its last colors.
+    pub fn unbind_effect(&mut self, z_index: i32) -> Option<Box<dyn Effect>> {
+        self.effect_bindings
+            .remove(&z_index)
+            .map(|binding| binding.effect)
+    }
+
+    /// Time since the runtime started according to its clock.
+    pub fn elapsed(&self) -> Duration {
+        self.clock.elapsed(self.start)
+    }
+
+    /// Run the process loop at the rate of the scheduler
+    pub fn run(&mut self, process: &mut T) {
+        self.begin(process);
+        loop {
+            self.scheduler.wait_for_frame(&self.clock);
+            let start = self.clock.now();
+            let running = self.step(process);
+            self.scheduler.frame_done(start, self.clock.now());
+            if !running {
+                break;
+            }
+        }
+    }
+
+    /// Reset the runtime and initialise the process.
+    /// Use this with `step` to drive the loop yourself, `run` does both.
+    pub fn begin(&mut self, process: &mut T) {
+        assert!(self.backend.is_connected(), "Output device not connected");
         // reset state to default
         self.exit = false;
         self.effect_layers.clear();
+        self.effect_bindings.clear();
         self.timers.clear();
         self.tweeners.clear();
-        self.start = Instant::now();
+        self.start = self.clock.now();
+        self.scheduler
+            .reset(self.backend.capabilities().max_update_rate);
+        self.frame_diff = FrameDiff::new();
+        self.dither.reset();
 
         process.init(self);
-        let mut last = Instant::now();
-        loop {
-            let now = Instant::now();
-            let delta = now.duration_since(last);
-            self.delta = delta;
-            last = now;
-
-            let mut tweeners = self.tweeners.clone();
-            tweeners.retain(|(tweener, callback, finished)| {
-                let value = tweener.borrow_mut().move_by(delta.as_secs_f64());
-                let retain = callback(self, process, value);
-                if let Some(finished) = finished
-                    && tweener.borrow().is_finished()
-                {
-                    finished(self, process, value);
-                }
-                !tweener.borrow().is_finished() && retain
-            });
-            self.tweeners = tweeners;
-
-            // for timer in self.timers.clone().iter() {
-            //     let mut timer = timer.borrow_mut();
-            //     if timer.is_finished() {
-            //         timer.timeout(self, process);
-            //     }
-            // }
-
-            let mut timers = self.timers.clone();
-            timers.retain(|timer| {
-                let mut timer = timer.borrow_mut();
-                if timer.is_finished() {
-                    timer.timeout(self, process);
-                }
+        self.last = self.clock.now();
+    }
+
+    /// Run a single iteration of the process loop without waiting for the scheduler.
+    /// Returns false once the runtime has exited.
+    pub fn step(&mut self, process: &mut T) -> bool {
+        let now = self.clock.now();
+        let delta = now.duration_since(self.last);
+        self.delta = delta;
+        self.last = now;
+
+        if let Some(delta_watcher) = &self.delta_watcher {
+            delta_watcher.poll(delta);
+        }
 
-                !timer.is_finished() || !timer.one_shot || !timer.continue_running
-            });
-            self.timers = timers;
+        let time = self.elapsed().as_secs_f64();
+        for binding in self.effect_bindings.values_mut() {
+            binding.effect.update(time);
+        }
 
-            process.process(self, delta);
+        let mut tweeners = self.tweeners.clone();
+        tweeners.retain(|(tweener, callback, finished)| {
+            let value = tweener.borrow_mut().move_by(delta.as_secs_f64());
+            let retain = callback(self, process, value);
+            if let Some(finished) = finished
+                && tweener.borrow().is_finished()
+            {
+                finished(self, process, value);
+            }
+            !tweener.borrow().is_finished() && retain
+        });
+        self.tweeners = tweeners;
 
-            if let Some(delta_watcher) = &self.delta_watcher {
-                delta_watcher.just_pressed_consume();
+        let mut timers = self.timers.clone();
+        timers.retain(|timer| {
+            let mut timer = timer.borrow_mut();
+            if timer.is_finished() {
+                timer.timeout(self, process);
             }
 
-            if self.exit {
-                break;
+            !timer.is_finished() || !timer.one_shot || !timer.continue_running
+        });
+        self.timers = timers;
+
+        if let Some(delta_watcher) = &self.delta_watcher {
+            for event in delta_watcher.events() {
+                process.input(self, event);
             }
         }
+
+        process.process(self, delta);
+
+        if self.scheduler.render_due(self.clock.now()) {
+            self.update_keyboard();
+        }
+
+        if let Some(delta_watcher) = &self.delta_watcher {
+            delta_watcher.just_pressed_consume();
+        }
+
+        !self.exit
     }
 
     /// Exit the process loop after this iteration.
@@ -169,50 +427,106 @@ impl<T: Process<Owner = Self>> Runtime<T> {
         one_shot: bool,
         callback: U,
     ) {
-        self.timers.push(Rc::new(RefCell::new(Timer::start(
-            timeout,
-            one_shot,
-            Box::new(callback),
-        ))));
+        self.timers
+            .push(Rc::new(RefCell::new(Timer::start_with_clock(
+                self.clock.clone(),
+                timeout,
+                one_shot,
+                Box::new(callback),
+            ))));
     }
 
     /// Updates the keyboard rgb array.
     /// This is quite intensive due to keyboard communication. (around 16 ms)
+    /// Use `render_in_background` to not block the process loop.
     pub fn update_keyboard(&mut self) {
-        let mut render = self.render_layer;
+        let time = self.elapsed().as_secs_f64();
+        for (z_index, binding) in self.effect_bindings.iter() {
+            if let Some(layer) = self.effect_layers.get_mut(z_index)
+                && layer.visible
+            {
+                for key in layer.matrix.keys_mut() {
+                    (key.color, key.alpha) = binding.evaluate(time, key);
+                }
+            }
+        }
 
-        let mut ks = self.effect_layers.keys().copied().collect::<Vec<_>>();
-        ks.sort();
+        // Premultiplied color and alpha of every key, starting from the black keyboard
+        let mut render = self.render_layer.clone();
 
         {
-            let render = render.as_flattened_mut();
-            for k in ks {
-                let layer = *self.get_layer(k);
-                for (i, key) in layer.as_flattened().iter().enumerate() {
-                    match key.color_blend_type {
-                        ColorBlendTypes::Add => render[i].color += key.color,
-                        ColorBlendTypes::Sub => render[i].color -= key.color,
-                        ColorBlendTypes::Mult => {
-                            render[i].color.red *= key.color.red;
-                            render[i].color.green *= key.color.green;
-                            render[i].color.blue *= key.color.blue;
-                        }
-                        ColorBlendTypes::Mask => render[i].color = key.color,
-                        ColorBlendTypes::Nothing => (),
-                        ColorBlendTypes::AlphaBlend(lower, upper) => {
-                            render[i].color *= lower;
-                            render[i].color += key.color * upper;
-                        }
+            for k in self.layer_indices() {
+                let layer = &self.effect_layers[&k];
+                if !layer.visible {
+                    continue;
+                }
+                for key in layer.matrix.keys() {
+                    // Layers of another size only cover the keys they have
+                    let Some(render_key) = render.get_mut(key.key) else {
+                        continue;
+                    };
+                    let backdrop_alpha = render_key.alpha;
+                    let lower = if backdrop_alpha > 0.0 {
+                        render_key.color / backdrop_alpha
+                    } else {
+                        render_key.color
                     };
+                    let upper = if self.linear_compositing {
+                        to_linear(key.color)
+                    } else {
+                        key.color
+                    };
+                    let blended = match key.color_blend_type {
+                        ColorBlendTypes::Custom(id) => self
+                            .blend_modes
+                            .get(&id)
+                            .map(|mode| mode.blend(lower, upper))
+                            .unwrap_or(lower),
+                        mode => mode.blend(lower, upper),
+                    };
+                    // Source over with premultiplied alpha.
+                    // Only the part of the key that covers something below is blended.
+                    let source = upper * (1.0 - backdrop_alpha) + blended * backdrop_alpha;
+                    let alpha = key.alpha.clamp(0.0, 1.0) * layer.opacity.clamp(0.0, 1.0);
+                    render_key.color = source * alpha + render_key.color * (1.0 - alpha);
+                    render_key.alpha = alpha + backdrop_alpha * (1.0 - alpha);
                 }
             }
         }
 
-        for key in render.as_flattened() {
-            let (red, green, blue) = key.colors();
-            self.keyboard.array_set_single(key.key, red, green, blue);
+        let frame = render
+            .keys()
+            .map(|key| {
+                // Premultiplied color is the same as the color over black
+                let color = if self.linear_compositing {
+                    from_linear(key.color)
+                } else {
+                    key.color
+                };
+                let color = if self.dithering {
+                    self.dither.quantize(key.key, self.transfer.apply(color))
+                } else {
+                    self.transfer.to_rgb8(color)
+                };
+                (key.key, color)
+            })
+            .collect::<Vec<KeyColor>>();
+        if self.skip_unchanged {
+            let changes = self.frame_diff.changes(&frame);
+            if changes.is_empty() {
+                return;
+            }
+            self.backend.set_frame(&changes);
+        } else {
+            self.frame_diff.record_full(&frame);
+            self.backend.set_frame(&frame);
         }
-        self.keyboard.array_update();
+        self.backend.flush();
+    }
+
+    /// How many keyboard writes were avoided by skipping unchanged keys.
+    pub fn output_stats(&self) -> OutputStats {
+        self.frame_diff.stats
     }
 }
 
@@ -223,4 +537,10 @@ pub trait Process {
     fn init(&mut self, runtime: &mut Self::Owner);
     /// Called every step of the loop
     fn process(&mut self, runtime: &mut Self::Owner, delta: Duration);
+    /// Called for every key event before `process` in the order they happened.
+    /// Needs a runtime with analog support.
+    /// Events wait in a queue of `EVENT_QUEUE_LEN` between loops. If more happen than that,
+    /// the rest are dropped, releases too, and counted in `DeltaWatcher::dropped_events`.
+    /// Check `KeyDelta::is_pressed` when a missed release would leave something stuck.
+    fn input(&mut self, _runtime: &mut Self::Owner, _event: KeyEvent) {}
 }
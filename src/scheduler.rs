@@ -0,0 +1,191 @@
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+
+/// What to do when the process loop falls behind its target rate.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUpPolicy {
+    /// Drop the missed frames and continue from now.
+    #[default]
+    Skip,
+    /// Run the missed frames back to back without waiting.
+    /// At most this many frames are caught up, the rest are dropped.
+    CatchUp(u32),
+}
+
+/// Frame time statistics of the process loop.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// Process frames run
+    pub frames: u64,
+    /// Frames rendered to the keyboard by the scheduler
+    pub renders: u64,
+    /// Frames dropped because the loop fell behind
+    pub skipped: u64,
+    /// How long the latest frame took to run
+    pub last_frame_time: Duration,
+    /// Moving average of how long frames take to run
+    pub average_frame_time: Duration,
+    /// Longest frame so far
+    pub max_frame_time: Duration,
+    /// Moving average of time between the starts of frames
+    pub average_interval: Duration,
+}
+
+impl FrameStats {
+    /// Actual process frames per second.
+    /// 0 until two frames have run.
+    pub fn rate(&self) -> f64 {
+        if self.average_interval.is_zero() {
+            0.0
+        } else {
+            1.0 / self.average_interval.as_secs_f64()
+        }
+    }
+
+    fn record(&mut self, frame_time: Duration, interval: Option<Duration>) {
+        const SMOOTHING: f64 = 0.1;
+
+        self.frames += 1;
+        self.last_frame_time = frame_time;
+        self.max_frame_time = self.max_frame_time.max(frame_time);
+        self.average_frame_time = if self.frames == 1 {
+            frame_time
+        } else {
+            self.average_frame_time.mul_f64(1.0 - SMOOTHING) + frame_time.mul_f64(SMOOTHING)
+        };
+        if let Some(interval) = interval {
+            self.average_interval = if self.average_interval.is_zero() {
+                interval
+            } else {
+                self.average_interval.mul_f64(1.0 - SMOOTHING) + interval.mul_f64(SMOOTHING)
+            };
+        }
+    }
+}
+
+/// Paces the process loop and keyboard rendering of the runtime.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    /// How many times per second process is called.
+    /// None, or a rate that isn't above 0, runs the loop as fast as possible.
+    pub process_rate: Option<f64>,
+    /// How many times per second the keyboard is updated automatically.
+    /// Limited by the update rate of the output backend.
+    /// None leaves updating to the process with `update_keyboard`.
+    /// A rate that isn't above 0 updates it on every frame.
+    pub render_rate: Option<f64>,
+    /// What to do when the process loop falls behind.
+    pub policy: CatchUpPolicy,
+    /// Frame time statistics since the runtime started.
+    pub stats: FrameStats,
+    max_render_rate: Option<f64>,
+    next_process: Option<Instant>,
+    next_render: Option<Instant>,
+    last_frame: Option<Instant>,
+}
+
+impl Scheduler {
+    pub fn new(process_rate: Option<f64>, render_rate: Option<f64>) -> Self {
+        Self {
+            process_rate,
+            render_rate,
+            policy: CatchUpPolicy::Skip,
+            stats: FrameStats::default(),
+            max_render_rate: None,
+            next_process: None,
+            next_render: None,
+            last_frame: None,
+        }
+    }
+
+    /// Reset the schedule and statistics.
+    pub(crate) fn reset(&mut self, max_render_rate: Option<f64>) {
+        self.stats = FrameStats::default();
+        self.max_render_rate = max_render_rate;
+        self.next_process = None;
+        self.next_render = None;
+        self.last_frame = None;
+    }
+
+    /// Sleep until the next process frame is due.
+    pub(crate) fn wait_for_frame(&mut self, clock: &Clock) {
+        let Some(rate) = self.process_rate else {
+            return;
+        };
+        let interval = interval(rate);
+        if interval.is_zero() {
+            return;
+        }
+        let now = clock.now();
+        let next = *self.next_process.get_or_insert(now);
+
+        if next > now {
+            clock.sleep(next - now);
+            self.next_process = Some(next + interval);
+            return;
+        }
+
+        let missed = ((now - next).as_nanos() / interval.as_nanos().max(1)) as u64;
+        self.next_process = Some(match self.policy {
+            CatchUpPolicy::Skip => {
+                self.stats.skipped += missed;
+                now + interval
+            }
+            CatchUpPolicy::CatchUp(max) if missed > max as u64 => {
+                self.stats.skipped += missed - max as u64;
+                now - interval * max + interval
+            }
+            CatchUpPolicy::CatchUp(_) => next + interval,
+        });
+    }
+
+    /// Record a frame that started at `start`.
+    pub(crate) fn frame_done(&mut self, start: Instant, end: Instant) {
+        let interval = self.last_frame.map(|last| start.duration_since(last));
+        self.last_frame = Some(start);
+        self.stats.record(end.duration_since(start), interval);
+    }
+
+    /// Whether the keyboard should be rendered now.
+    pub(crate) fn render_due(&mut self, now: Instant) -> bool {
+        let Some(mut rate) = self.render_rate else {
+            return false;
+        };
+        if let Some(max) = self.max_render_rate {
+            rate = rate.min(max);
+        }
+        let interval = interval(rate);
+        let next = *self.next_render.get_or_insert(now);
+        if now < next {
+            return false;
+        }
+
+        // Renders are never caught up
+        self.next_render = Some((next + interval).max(now));
+        self.stats.renders += 1;
+        true
+    }
+}
+
+/// Longest time between frames, slower rates are limited to this
+const MAX_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Time between frames at the rate.
+/// Rates that aren't above 0 (or NaN) are unlimited.
+fn interval(rate: f64) -> Duration {
+    if rate > 0.0 {
+        Duration::try_from_secs_f64(1.0 / rate)
+            .unwrap_or(MAX_INTERVAL)
+            .min(MAX_INTERVAL)
+    } else {
+        Duration::ZERO
+    }
+}
+
+impl Default for Scheduler {
+    /// Process at 100 frames per second and leave rendering to the process.
+    fn default() -> Self {
+        Self::new(Some(100.0), None)
+    }
+}
@@ -1,6 +1,9 @@
 use std::time::{Duration, Instant};
 
-use crate::process::{Process, Runtime};
+use crate::{
+    clock::Clock,
+    process::{Process, Runtime},
+};
 
 pub type TimerCallback<T> = Box<dyn Fn(&mut Runtime<T>, &mut T) -> bool>;
 
@@ -14,12 +17,24 @@ pub struct Timer<T: Process<Owner = Runtime<T>>> {
     elapsed: Instant,
     timeout: Duration,
     callback: TimerCallback<T>,
+    clock: Clock,
 }
 
 impl<T: Process<Owner = Runtime<T>>> Timer<T> {
     pub fn start(timeout: Duration, one_shot: bool, callback: TimerCallback<T>) -> Self {
+        Self::start_with_clock(Clock::real(), timeout, one_shot, callback)
+    }
+
+    /// Start a timer which follows the given clock.
+    pub fn start_with_clock(
+        clock: Clock,
+        timeout: Duration,
+        one_shot: bool,
+        callback: TimerCallback<T>,
+    ) -> Self {
         Self {
-            elapsed: Instant::now(),
+            elapsed: clock.now(),
+            clock,
             continue_running: true,
             timeout,
             one_shot,
@@ -29,14 +44,14 @@ impl<T: Process<Owner = Runtime<T>>> Timer<T> {
 
     /// Whether given time has elapsed
     pub fn is_finished(&self) -> bool {
-        self.elapsed.elapsed() >= self.timeout
+        self.clock.elapsed(self.elapsed) >= self.timeout
     }
 
     /// Run a timeout
     pub fn timeout(&mut self, runtime: &mut Runtime<T>, process: &mut T) {
         // reset timer
         if !self.one_shot {
-            self.elapsed = Instant::now();
+            self.elapsed = self.clock.now();
         }
         // call the callback
         self.continue_running = (self.callback)(runtime, process);
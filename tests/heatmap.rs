@@ -0,0 +1,80 @@
+use nalgebra::Vector2;
+use rgb_engine::{
+    Coordinates, Effect,
+    effects::analog::{Decay, Heatmap},
+    keyboard::{DeltaWatcher, current_layout},
+    re_exports::palette::Srgb,
+};
+
+fn csv_line(label: &str, quoted: &str, presses: f64) -> String {
+    let key = current_layout().key_by_label(label).unwrap().matrix;
+    format!("{},{},{quoted},{presses},0\n", key.0, key.1)
+}
+
+#[test]
+fn csv_labels_are_quoted() {
+    let text = format!(
+        "row,column,label,presses,travel\n{}",
+        csv_line(",", "\",\"", 2.0)
+    );
+    let mut heatmap = Heatmap::new(DeltaWatcher::dummy());
+    heatmap.parse_csv(&text).unwrap();
+    assert_eq!(heatmap.to_csv(), text);
+}
+
+#[test]
+fn csv_of_another_layout_is_rejected() {
+    let key = current_layout().key_by_label("Esc").unwrap().matrix;
+    let text = format!(
+        "row,column,label,presses,travel\n{}{},{},Q,1,0\n",
+        csv_line("A", "A", 1.0),
+        key.0,
+        key.1
+    );
+    let mut heatmap = Heatmap::new(DeltaWatcher::dummy());
+    assert!(heatmap.parse_csv(&text).is_err());
+    assert!(heatmap.usage().is_empty());
+}
+
+#[test]
+fn invalid_half_life_does_not_decay() {
+    let mut heatmap = Heatmap::new(DeltaWatcher::dummy());
+    heatmap
+        .parse_csv(&format!("header\n{}", csv_line("A", "A", 4.0)))
+        .unwrap();
+    heatmap.decay = Decay::HalfLife(-1.0);
+    heatmap.update(0.0);
+    heatmap.update(1.0);
+    let key = current_layout().key_by_label("A").unwrap().matrix;
+    assert_eq!(heatmap.key_usage(key).presses, 4.0);
+}
+
+#[test]
+fn keys_are_colored_by_heat() {
+    let mut heatmap = Heatmap::new(DeltaWatcher::dummy());
+    heatmap.gradient = vec![Srgb::new(0.0, 0.0, 0.0), Srgb::new(1.0, 0.0, 0.0)];
+    heatmap
+        .parse_csv(&format!(
+            "header\n{}{}",
+            csv_line("A", "A", 4.0),
+            csv_line("S", "S", 2.0)
+        ))
+        .unwrap();
+
+    let matrix = heatmap.delta_watcher.mat_keys.clone();
+    let color = |label: &str| {
+        heatmap
+            .key_color(0.0, matrix.by_label(label).unwrap())
+            .unwrap()
+    };
+    assert_eq!(color("A"), Srgb::new(1.0, 0.0, 0.0));
+    assert_eq!(color("S"), Srgb::new(0.5, 0.0, 0.0));
+    assert_eq!(color("D"), Srgb::new(0.0, 0.0, 0.0));
+    // Sampled directly, off the centre of a key
+    heatmap.coordinates = Coordinates::AspectCorrected;
+    let a = Coordinates::AspectCorrected.position(matrix.by_label("A").unwrap());
+    assert_eq!(
+        heatmap.color(0.0, a + Vector2::new(0.001, 0.0)),
+        Srgb::new(1.0, 0.0, 0.0)
+    );
+}
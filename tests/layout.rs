@@ -0,0 +1,49 @@
+use rgb_engine::keyboard::{KeyGroup, KeycodeType, Layout, Translation, key_positions};
+
+#[test]
+fn built_in_layouts_roundtrip() {
+    for layout in [
+        Layout::wooting_60he(),
+        Layout::wooting_two_he(),
+        Layout::wooting_80he(),
+        Layout::wooting_uwu(),
+    ] {
+        assert_eq!(Layout::parse(&layout.to_text()), Ok(layout));
+    }
+}
+
+#[test]
+fn positions_outside_of_the_keyboard_are_rejected() {
+    assert!(Layout::parse("5 20 0 0 1 1 Last").is_ok());
+    assert!(Layout::parse("6 0 0 0 1 1 Row").is_err());
+    assert!(Layout::parse("0 21 0 0 1 1 Column").is_err());
+}
+
+#[test]
+fn labels_resolve_against_one_layout() {
+    let layout = Layout::wooting_60he();
+    let positions = layout.positions_of(&["Esc", "Missing", "Fn"]);
+    assert_eq!(positions.len(), 2);
+    assert_eq!(positions[0], layout.key_by_label("Esc").unwrap().matrix);
+    assert_eq!(key_positions(&["Esc", "Fn"]), positions);
+}
+
+#[test]
+fn hid_code_of_43_depends_on_the_layout() {
+    let iso = Layout::wooting_60he();
+    assert!(iso.is_iso());
+    let ansi = Layout::parse("2 13 13 2 1 1 Backslash 43\n3 1 1 3 1 1 A 30").unwrap();
+    assert!(!ansi.is_iso());
+
+    let hid = |layout: &Layout, key| Translation::from_layout(layout, KeycodeType::Hid).get(key);
+    assert_eq!(hid(&ansi, 49), Some((2, 13)));
+    assert_eq!(hid(&ansi, 50), None);
+    assert_eq!(hid(&iso, 50), iso.key_by_label("'").map(|key| key.matrix));
+    assert_eq!(hid(&iso, 49), None);
+}
+
+#[test]
+fn uwu_has_no_arrow_keys() {
+    let layout = Layout::wooting_uwu();
+    assert!(layout.positions_of(KeyGroup::Arrows.labels()).is_empty());
+}
@@ -0,0 +1,331 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use rgb_engine::{
+    keyboard::{Actuation, DeltaWatcher, EVENT_QUEUE_LEN, ScriptedAnalog, get_matrix},
+    output::{Capabilities, MockBackend, OutputBackend, RecordedFrame, ThreadedBackend},
+    re_exports::palette::Srgb,
+    runtime::{Clock, ColorBlendTypes, FrameStats, Process, Runtime},
+};
+
+/// Fades the first layer to red over a second and exits.
+struct Fade;
+
+impl Process for Fade {
+    type Owner = Runtime<Self>;
+
+    fn init(&mut self, runtime: &mut Self::Owner) {
+        runtime.create_layer(0, get_matrix());
+        runtime.scheduler.render_rate = Some(10.0);
+    }
+
+    fn process(&mut self, runtime: &mut Self::Owner, _delta: Duration) {
+        let time = runtime.elapsed().as_secs_f64();
+        for key in runtime.get_layer(0).keys_mut() {
+            key.color = Srgb::new(time.min(1.0), 0.0, 0.0);
+        }
+        if time >= 1.0 {
+            runtime.exit();
+        }
+    }
+}
+
+fn run_fade() -> Vec<RecordedFrame> {
+    let clock = Clock::manual();
+    let mock = MockBackend::with_clock(clock.clone());
+    let mut runtime = Runtime::with_delta_watcher(mock.clone(), None);
+    runtime.clock = clock;
+    runtime.run(&mut Fade);
+    mock.frames()
+}
+
+#[test]
+fn frames_follow_manual_clock() {
+    let frames = run_fade();
+    assert_eq!(frames.len(), 11);
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(frame.time, Duration::from_millis(100 * i as u64));
+    }
+    assert_eq!(frames[5].color((1, 0)), Some((128, 0, 0)));
+    let last = frames.last().unwrap();
+    assert_eq!(last.color((1, 0)), Some((255, 0, 0)));
+}
+
+#[test]
+fn manual_clock_is_deterministic() {
+    assert_eq!(run_fade(), run_fade());
+}
+
+/// Counts timer timeouts and presses of the scripted key.
+#[derive(Default)]
+struct Counter {
+    steps: u32,
+    timeouts: u32,
+    presses: u32,
+}
+
+impl Process for Counter {
+    type Owner = Runtime<Self>;
+
+    fn init(&mut self, runtime: &mut Self::Owner) {
+        runtime.create_layer(0, get_matrix());
+        runtime.create_timer(Duration::from_millis(100), false, |_runtime, process| {
+            process.timeouts += 1;
+            true
+        });
+    }
+
+    fn process(&mut self, runtime: &mut Self::Owner, _delta: Duration) {
+        let delta_watcher = runtime.delta_watcher.as_ref().unwrap();
+        if delta_watcher.keys.iter().any(|key| key.just_pressed()) {
+            self.presses += 1;
+        }
+        self.steps += 1;
+        if self.steps == 50 {
+            runtime.exit();
+        }
+    }
+}
+
+#[test]
+fn scripted_input_and_timers() {
+    let script = ScriptedAnalog::new()
+        .idle(2)
+        .hold(&[(57, 1.0)], 3)
+        .idle(1)
+        .frame(&[(57, 0.9)]);
+    let mut runtime =
+        Runtime::with_delta_watcher(MockBackend::new(), Some(DeltaWatcher::polled(script, 127)));
+    runtime.clock = Clock::manual();
+    let mut counter = Counter::default();
+    runtime.run(&mut counter);
+
+    assert_eq!(counter.presses, 2);
+    // 50 steps at the default 100 hz
+    assert_eq!(runtime.elapsed(), Duration::from_millis(490));
+    assert_eq!(counter.timeouts, 4);
+}
+
+/// Runs a few frames with the given rates.
+struct Rates {
+    process_rate: Option<f64>,
+    render_rate: Option<f64>,
+    steps: u32,
+}
+
+impl Process for Rates {
+    type Owner = Runtime<Self>;
+
+    fn init(&mut self, runtime: &mut Self::Owner) {
+        runtime.create_layer(0, get_matrix());
+        runtime.scheduler.process_rate = self.process_rate;
+        runtime.scheduler.render_rate = self.render_rate;
+    }
+
+    fn process(&mut self, runtime: &mut Self::Owner, _delta: Duration) {
+        self.steps += 1;
+        if self.steps == 5 {
+            runtime.exit();
+        }
+    }
+}
+
+#[test]
+fn invalid_rates_are_unlimited() {
+    assert_eq!(FrameStats::default().rate(), 0.0);
+
+    for rate in [0.0, -10.0, f64::NAN, f64::INFINITY, 1e-300] {
+        let mut runtime = Runtime::with_delta_watcher(MockBackend::new(), None);
+        runtime.clock = Clock::manual();
+        let mut process = Rates {
+            process_rate: Some(rate),
+            render_rate: Some(rate),
+            steps: 0,
+        };
+        runtime.run(&mut process);
+        assert_eq!(process.steps, 5);
+    }
+}
+
+/// Creates named layers.
+struct Named;
+
+impl Process for Named {
+    type Owner = Runtime<Self>;
+
+    fn init(&mut self, runtime: &mut Self::Owner) {
+        assert!(runtime.create_named_layer("base", 0, get_matrix()).is_ok());
+        assert!(runtime.create_named_layer("base", 1, get_matrix()).is_err());
+        // Replacing the layer with the name is fine
+        assert!(runtime.create_named_layer("base", 0, get_matrix()).is_ok());
+        assert!(runtime.create_named_layer("top", 1, get_matrix()).is_ok());
+        assert_eq!(runtime.layer_index("base"), Some(0));
+        assert_eq!(runtime.layer_index("top"), Some(1));
+        runtime.exit();
+    }
+
+    fn process(&mut self, _runtime: &mut Self::Owner, _delta: Duration) {}
+}
+
+#[test]
+fn layer_names_are_unique() {
+    let mut runtime = Runtime::with_delta_watcher(MockBackend::new(), None);
+    runtime.clock = Clock::manual();
+    runtime.run(&mut Named);
+}
+
+/// Red under half transparent blue under a green layer at half opacity.
+struct Stack;
+
+impl Process for Stack {
+    type Owner = Runtime<Self>;
+
+    fn init(&mut self, runtime: &mut Self::Owner) {
+        runtime.create_layer(0, get_matrix());
+        runtime.create_layer(1, get_matrix());
+        runtime.create_layer(2, get_matrix());
+        for key in runtime.get_layer(0).keys_mut() {
+            key.color = Srgb::new(1.0, 0.0, 0.0);
+        }
+        for key in runtime.get_layer(1).keys_mut() {
+            key.color = Srgb::new(0.0, 0.0, 1.0);
+            key.alpha = 0.5;
+            key.color_blend_type = ColorBlendTypes::Mask;
+        }
+        // Only the first key is covered by the top layer
+        runtime.get_layer(2)[(1, 0)].color = Srgb::new(0.0, 1.0, 0.0);
+        runtime.get_layer(2)[(1, 0)].color_blend_type = ColorBlendTypes::Mask;
+        for key in runtime.get_layer(2).keys_mut() {
+            if key.key != (1, 0) {
+                key.alpha = 0.0;
+            }
+        }
+        runtime.set_layer_opacity(2, 0.5);
+    }
+
+    fn process(&mut self, _runtime: &mut Self::Owner, _delta: Duration) {}
+}
+
+#[test]
+fn layers_are_composited_over() {
+    let mock = MockBackend::new();
+    let mut runtime = Runtime::with_delta_watcher(mock.clone(), None);
+    runtime.clock = Clock::manual();
+    runtime.begin(&mut Stack);
+    runtime.update_keyboard();
+    let frame = mock.frames().pop().unwrap();
+    assert_eq!(frame.color((1, 0)), Some((64, 128, 64)));
+    assert_eq!(frame.color((1, 1)), Some((128, 0, 128)));
+}
+
+#[test]
+fn full_event_queue_counts_drops() {
+    let mut script = ScriptedAnalog::new();
+    for _ in 0..EVENT_QUEUE_LEN {
+        script = script.frame(&[(57, 1.0)]).idle(1);
+    }
+    let watcher = DeltaWatcher::polled(script, 127);
+    for _ in 0..EVENT_QUEUE_LEN * 2 {
+        watcher.poll(Duration::from_millis(10));
+    }
+
+    assert_eq!(watcher.events().len(), EVENT_QUEUE_LEN);
+    assert_eq!(
+        watcher.dropped_events.load(Ordering::Relaxed),
+        EVENT_QUEUE_LEN as u64
+    );
+}
+
+#[test]
+fn travel_updates_without_time_passing() {
+    let script = ScriptedAnalog::new().frame(&[(57, 0.5)]).idle(1);
+    let watcher = DeltaWatcher::polled(script, 127);
+    watcher.poll(Duration::ZERO);
+    watcher.poll(Duration::ZERO);
+
+    let key = watcher.keys.iter().find(|key| key.scan_code == 57).unwrap();
+    assert_eq!(key.total_travel(), 1.0);
+    assert_eq!(key.velocity(), 0.0);
+}
+
+#[test]
+fn keystroke_history_is_capped() {
+    let mut script = ScriptedAnalog::new();
+    for depth in [0.2, 0.4, 0.6, 0.8, 1.0] {
+        script = script.frame(&[(57, depth)]).idle(1);
+    }
+    let watcher = DeltaWatcher::polled(script, 127);
+    watcher.keystroke_history.store(2, Ordering::Relaxed);
+    for _ in 0..10 {
+        watcher.poll(Duration::from_millis(10));
+    }
+
+    let key = watcher.keys.iter().find(|key| key.scan_code == 57).unwrap();
+    let strokes = watcher.keystrokes(key.key);
+    assert_eq!(strokes.len(), 2);
+    assert!((strokes[0].max_depth - 0.8).abs() < 0.01);
+    assert_eq!(strokes[1].max_depth, 1.0);
+}
+
+#[test]
+fn mock_with_capabilities_uses_the_clock() {
+    let clock = Clock::manual();
+    let mut mock = MockBackend::with_capabilities(
+        clock.clone(),
+        Capabilities {
+            rows: 6,
+            columns: 21,
+            max_update_rate: Some(30.0),
+        },
+    );
+    clock.advance(Duration::from_secs(5));
+    mock.flush();
+    assert_eq!(mock.capabilities().max_update_rate, Some(30.0));
+    assert_eq!(mock.last_frame().unwrap().time, Duration::from_secs(5));
+}
+
+#[test]
+fn threaded_watcher_uses_the_clock() {
+    let script = ScriptedAnalog::new().idle(5).hold(&[(57, 1.0)], 1000);
+    let watcher =
+        DeltaWatcher::with_source(script, Duration::from_millis(1), 10, 127, Clock::manual());
+    let events = watcher.subscribe();
+    let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+    watcher.exit.store(true, Ordering::Relaxed);
+
+    // No time passes on the manual clock
+    assert_eq!(event.time, Duration::ZERO);
+}
+
+#[test]
+fn threaded_backend_merges_pending_keys() {
+    let mock = MockBackend::new();
+    let mut threaded = ThreadedBackend::new(mock.clone());
+    threaded.set_frame(&[((1, 0), (1, 1, 1)), ((1, 1), (2, 2, 2))]);
+    threaded.set_frame(&[((1, 1), (3, 3, 3)), ((1, 2), (4, 4, 4))]);
+    threaded.flush();
+    // Writes the waiting frame
+    drop(threaded);
+
+    let frame = mock.last_frame().unwrap();
+    assert_eq!(frame.color((1, 0)), Some((1, 1, 1)));
+    assert_eq!(frame.color((1, 1)), Some((3, 3, 3)));
+    assert_eq!(frame.color((1, 2)), Some((4, 4, 4)));
+}
+
+#[test]
+fn zero_actuation_does_not_toggle_at_rest() {
+    let watcher = DeltaWatcher::polled(ScriptedAnalog::new().hold(&[(57, 0.0)], 10), 0);
+    watcher.set_actuation(Actuation {
+        press: 0,
+        reset: 0,
+        rapid_trigger: Some(0),
+    });
+    for _ in 0..10 {
+        watcher.poll(Duration::from_millis(10));
+    }
+    assert!(watcher.events().is_empty());
+
+    let key = watcher.keys.iter().find(|key| key.scan_code == 57).unwrap();
+    assert_eq!(key.actuation(), Actuation::new(1).clamped());
+    assert!(!key.is_pressed());
+}
@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use rgb_engine::keyboard::{MAX_SPEED, MIN_SPEED, Session, SessionFrame, SessionReplay};
+
+fn session() -> Session {
+    Session {
+        frames: vec![
+            SessionFrame {
+                time: Duration::from_millis(10),
+                changes: vec![(30, 128), (57435, 255)],
+            },
+            SessionFrame {
+                time: Duration::from_millis(25),
+                changes: vec![(30, 0)],
+            },
+        ],
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let mut bytes = Vec::new();
+    session().write_to(&mut bytes).unwrap();
+    assert_eq!(Session::read_from(&bytes[..]).unwrap(), session());
+}
+
+#[test]
+fn truncated_file_is_an_error() {
+    let mut bytes = Vec::new();
+    session().write_to(&mut bytes).unwrap();
+    // Lengths where the file ends between frames
+    let mut boundaries = Vec::new();
+    for frames in 0..=session().frames.len() {
+        let mut partial = Vec::new();
+        Session {
+            frames: session().frames[..frames].to_vec(),
+        }
+        .write_to(&mut partial)
+        .unwrap();
+        boundaries.push(partial.len());
+    }
+
+    for len in 5..=bytes.len() {
+        let result = Session::read_from(&bytes[..len]);
+        assert_eq!(result.is_ok(), boundaries.contains(&len), "{len} bytes");
+    }
+}
+
+#[test]
+fn replay_speed_is_limited() {
+    assert_eq!(SessionReplay::new(session(), -1.0).speed(), MIN_SPEED);
+    assert_eq!(
+        SessionReplay::new(session(), f64::INFINITY).speed(),
+        MAX_SPEED
+    );
+    assert_eq!(SessionReplay::new(session(), f64::NAN).speed(), 1.0);
+
+    let mut replay = SessionReplay::new(session(), 2.0);
+    assert_eq!(replay.speed(), 2.0);
+    replay.set_speed(0.0);
+    assert_eq!(replay.speed(), MIN_SPEED);
+}
+
+#[test]
+fn overflowing_time_is_an_error() {
+    let mut bytes = b"RGBS\x01".to_vec();
+    // Longest possible delta and no changes, enough times to overflow the time
+    let frame = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00];
+    for _ in 0..1_100_000 {
+        bytes.extend_from_slice(&frame);
+    }
+    let err = Session::read_from(&bytes[..]).unwrap_err();
+    assert_eq!(err.to_string(), "Session too long");
+}